@@ -0,0 +1,90 @@
+extern crate krab_language;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use krab_language::ast::Stmt;
+use krab_language::interpreter::{Engine, Interpreter};
+use krab_language::lexer::{Lexer, Token};
+use krab_language::parser::Parser;
+
+const ENGINES: [Engine; 2] = [Engine::TreeWalker, Engine::Bytecode];
+
+fn lex(source: &str) -> Vec<Token> {
+    Lexer::new().lex(source).unwrap()
+}
+
+fn parse(source: &str) -> Vec<Stmt> {
+    Parser::new().parse_instructions(lex(source)).unwrap()
+}
+
+/// A program of `lines` statements mixing every kind of token.
+fn large_source(lines: usize) -> String {
+    let mut source = String::new();
+    for i in 0..lines {
+        source.push_str(&format!("value_{} = (i + {}) * 2.5 - \"text\" >= 10 and not done; # comment #\n", i, i));
+    }
+    source
+}
+
+/// `depth` `while` loops nested in each other.
+fn nested_source(depth: usize) -> String {
+    let mut source = String::new();
+    for i in 0..depth {
+        source.push_str(&format!("while i{} < 10 {{ x = x + {}; ", i, i));
+    }
+    for _ in 0..depth {
+        source.push_str("}; ");
+    }
+    source
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    for lines in [100, 10_000] {
+        let source = large_source(lines);
+        group.bench_with_input(BenchmarkId::new("large_input", lines), &source, |b, source| {
+            b.iter(|| lex(black_box(source)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    for depth in [10, 100] {
+        let tokens = lex(&nested_source(depth));
+        group.bench_with_input(BenchmarkId::new("nested_blocks", depth), &tokens, |b, tokens| {
+            b.iter(|| Parser::new().parse_instructions(black_box(tokens.clone())).unwrap())
+        });
+    }
+    let tokens = lex(&large_source(1_000));
+    group.bench_function("large_input", |b| b.iter(|| Parser::new().parse_instructions(black_box(tokens.clone())).unwrap()));
+    group.finish();
+}
+
+/// Runs `source` on a fresh interpreter with every engine, the parsing not being measured.
+fn bench_program(c: &mut Criterion, name: &str, source: &str) {
+    let program = parse(source);
+    let mut group = c.benchmark_group(format!("interpreter/{}", name));
+    for engine in ENGINES {
+        group.bench_function(format!("{:?}", engine), |b| {
+            b.iter(|| {
+                let mut interpreter = Interpreter::with_engine(engine);
+                interpreter.execute_instructions(black_box(&program)).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_interpreter(c: &mut Criterion) {
+    bench_program(c, "loop", "i = 0; while i < 10000 { i = i + 1; };");
+    bench_program(c, "for_range", "total = 0; for i in 0..10000 { total = total + i; };");
+    bench_program(c, "nested_loops", "i = 0; while i < 100 { j = 0; while j < 100 { j = j + 1; }; i = i + 1; };");
+    bench_program(c, "string_repetition", "i = 0; while i < 1000 { s = \"abc\" * 100; i = i + 1; };");
+    bench_program(c, "variable_churn", "i = 0; while i < 1000 { a = i; b = a + 1; c = b * 2; d = c - a; e = d / 2; i = i + 1; };");
+    bench_program(c, "local_variables", "fn churn(n) { i = 0; total = 0; while i < n { step = i * 2; total = total + step - i; i = i + 1; }; return total; }; \
+        r = churn(10000);");
+    bench_program(c, "recursion", "fn fib(n) { if n < 2 { return n; }; return fib(n - 1) + fib(n - 2); }; r = fib(18);");
+}
+
+criterion_group!(benches, bench_lexer, bench_parser, bench_interpreter);
+criterion_main!(benches);
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::fmt::Display;
+use std::rc::Rc;
+use crate::span::Span;
+
+/// Statements run one after the other in their own scope.
+pub type Block = Vec<Stmt>;
+
+/// Where a variable is stored, filled in by `resolver` before the program runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Slot {
+    /// Looked up by name in the global variables, from a function or a statement not resolved yet.
+    #[default]
+    Global,
+    /// Index of a global variable, given to the top-level statements by the interpreter running them.
+    GlobalIndex(usize),
+    /// `(depth, index)` of the slot of the variable in the enclosing scopes of its function, `depth` counting the scopes
+    /// up from the current one. While the slot is not set the global variable of the same name is used instead, as a
+    /// function assigning a variable updates the global one when it exists, which is only known when it runs.
+    Local(usize, usize),
+}
+
+/// Expressions of the syntax tree, `span` pointing at their source when they were parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Variable { name: String, span: Option<Span>, slot: Slot },
+    /// Arithmetic, `operator` being one of `+ - * / %`.
+    Binary { left: Box<Expr>, operator: char, right: Box<Expr>, span: Option<Span> },
+    /// `-` or `!` applied to a single operand.
+    Unary { operator: char, operand: Box<Expr>, span: Option<Span> },
+    /// Comparison, `operator` being one of `> < ≥ ≤ = ≠`.
+    Comparison { left: Box<Expr>, operator: char, right: Box<Expr>, span: Option<Span> },
+    /// Short-circuit `&` (and) or `|` (or).
+    Logical { left: Box<Expr>, operator: char, right: Box<Expr>, span: Option<Span> },
+    Call { callee: Box<Expr>, arguments: Vec<Expr>, span: Option<Span> },
+    /// `start..end`, the integers from `start` included to `end` excluded.
+    Range { start: Box<Expr>, end: Box<Expr>, span: Option<Span> },
+    /// List literal, a new list being built each time it is evaluated.
+    List { elements: Vec<Expr>, span: Option<Span> },
+    /// Map literal of `key: value` entries, evaluated in order, a new map being built each time.
+    Map { entries: Vec<(Expr, Expr)>, span: Option<Span> },
+    Index { target: Box<Expr>, index: Box<Expr>, span: Option<Span> },
+    /// `target[start:end]`, a missing bound reaching the edge of the list.
+    Slice { target: Box<Expr>, start: Option<Box<Expr>>, end: Option<Box<Expr>>, span: Option<Span> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    Expr(Expr),
+    Assign { name: String, value: Expr, span: Option<Span>, slot: Slot },
+    /// `target[index] = value`, the operands being evaluated from left to right.
+    SetIndex { target: Expr, index: Expr, value: Expr, span: Option<Span> },
+    Block(Block),
+    /// `else_branch` is either a `Stmt::Block` or, for `else if`, another `Stmt::If`.
+    If { condition: Expr, then_block: Block, else_branch: Option<Box<Stmt>> },
+    While { condition: Expr, body: Block },
+    /// Runs `body` for each element of `iterable`, in a new scope where the element is stored in the first of the
+    /// `variables`. A map gives its keys, and its values too when there is a second variable.
+    For { variables: Vec<String>, iterable: Expr, body: Block, span: Option<Span> },
+    /// Declares the function in the variable `slot`.
+    Function(Rc<FunctionDef>, Slot),
+    Return { value: Expr, span: Option<Span> },
+    /// Leaves the innermost enclosing loop.
+    Break,
+    /// Skips the rest of the body of the innermost enclosing loop, moving on to its next iteration.
+    Continue,
+    /// Raises an error carrying `value`.
+    Throw { value: Expr, span: Option<Span> },
+    /// Runs `body`, then the `catch` handler if `body` raised an error, the handler getting its own scope holding the
+    /// error in the variable, and `finally` however the other blocks ended.
+    Try { body: Block, catch: Option<(String, Block)>, finally: Option<Block> },
+}
+
+/// A function declared in Krab, shared between the declaring statement and the function values created from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Block,
+    pub span: Option<Span>,
+}
+
+/// Source spelling of the operators stored as a single character.
+fn get_symbol(operator: char) -> String {
+    match operator {
+        '≥' => ">=".to_string(),
+        '≤' => "<=".to_string(),
+        '=' => "==".to_string(),
+        '≠' => "!=".to_string(),
+        '&' => "and".to_string(),
+        '|' => "or".to_string(),
+        _ => operator.to_string(),
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, expressions: &[Expr]) -> fmt::Result {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", expression)?;
+    }
+    Ok(())
+}
+
+/// Prints the expression back as Krab source, parenthesizing every operation.
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Integer(value) => write!(f, "{}", value),
+            Expr::Float(value) => write!(f, "{:?}", value),
+            Expr::String(value) => write!(f, "\"{}\"", value),
+            Expr::Boolean(value) => write!(f, "{}", value),
+            Expr::Null => write!(f, "null"),
+            Expr::Variable { name, .. } => write!(f, "{}", name),
+            Expr::Binary { left, operator, right, .. }
+            | Expr::Comparison { left, operator, right, .. }
+            | Expr::Logical { left, operator, right, .. } => write!(f, "({} {} {})", left, get_symbol(*operator), right),
+            Expr::Unary { operator, operand, .. } => write!(f, "{}{}", operator, operand),
+            Expr::Range { start, end, .. } => write!(f, "({}..{})", start, end),
+            Expr::Call { callee, arguments, .. } => {
+                write!(f, "{}(", callee)?;
+                write_list(f, arguments)?;
+                write!(f, ")")
+            },
+            Expr::List { elements, .. } => {
+                write!(f, "[")?;
+                write_list(f, elements)?;
+                write!(f, "]")
+            },
+            Expr::Map { entries, .. } => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
+            Expr::Index { target, index, .. } => write!(f, "{}[{}]", target, index),
+            Expr::Slice { target, start, end, .. } => {
+                write!(f, "{}[", target)?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "]")
+            },
+        }
+    }
+}
+
+fn write_block(f: &mut fmt::Formatter<'_>, block: &Block) -> fmt::Result {
+    write!(f, "{{")?;
+    for stmt in block {
+        write!(f, " {}", stmt)?;
+    }
+    write!(f, " }}")
+}
+
+/// Prints the statement back as Krab source on a single line, `;` included.
+impl Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Expr(expr) => write!(f, "{};", expr),
+            Stmt::Assign { name, value, .. } => write!(f, "{} = {};", name, value),
+            Stmt::SetIndex { target, index, value, .. } => write!(f, "{}[{}] = {};", target, index, value),
+            Stmt::Block(block) => {
+                write_block(f, block)?;
+                write!(f, ";")
+            },
+            Stmt::If { condition, then_block, else_branch } => {
+                write!(f, "if {} ", condition)?;
+                write_block(f, then_block)?;
+                match else_branch.as_deref() {
+                    Some(Stmt::Block(block)) => {
+                        write!(f, " else ")?;
+                        write_block(f, block)?;
+                        write!(f, ";")
+                    },
+                    Some(stmt) => write!(f, " else {}", stmt),
+                    None => write!(f, ";"),
+                }
+            },
+            Stmt::While { condition, body } => {
+                write!(f, "while {} ", condition)?;
+                write_block(f, body)?;
+                write!(f, ";")
+            },
+            Stmt::For { variables, iterable, body, .. } => {
+                write!(f, "for {} in {} ", variables.join(", "), iterable)?;
+                write_block(f, body)?;
+                write!(f, ";")
+            },
+            Stmt::Function(definition, _) => {
+                write!(f, "fn {}({}) ", definition.name, definition.parameters.join(", "))?;
+                write_block(f, &definition.body)?;
+                write!(f, ";")
+            },
+            Stmt::Return { value, .. } => write!(f, "return {};", value),
+            Stmt::Break => write!(f, "break;"),
+            Stmt::Continue => write!(f, "continue;"),
+            Stmt::Throw { value, .. } => write!(f, "throw {};", value),
+            Stmt::Try { body, catch, finally } => {
+                write!(f, "try ")?;
+                write_block(f, body)?;
+                if let Some((variable, handler)) = catch {
+                    write!(f, " catch {} ", variable)?;
+                    write_block(f, handler)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally ")?;
+                    write_block(f, finally)?;
+                }
+                write!(f, ";")
+            },
+        }
+    }
+}
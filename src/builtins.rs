@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use crate::errors::CustomError;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+
+/// Writer shared between the interpreter and the builtins printing to it.
+pub type Output = Rc<RefCell<Box<dyn Write>>>;
+
+/// Registers the standard builtins, `print` and `println` writing to `output`.
+pub fn register_builtins(interpreter: &mut Interpreter, output: Output) {
+    let print_output = Rc::clone(&output);
+    interpreter.register_variadic_function("print", move |arguments| write(&print_output, arguments, ""));
+    interpreter.register_variadic_function("println", move |arguments| write(&output, arguments, "\n"));
+    interpreter.register_function("len", 1, len);
+    interpreter.register_function("type_of", 1, |arguments| Ok(Value::new_string(arguments[0].get_type_name())));
+    interpreter.register_function("str", 1, |arguments| Ok(Value::new_string(&arguments[0]._to_string())));
+    interpreter.register_function("int", 1, int);
+    interpreter.register_function("float", 1, float);
+    interpreter.register_function("bool", 1, bool);
+    interpreter.register_function("keys", 1, keys);
+    interpreter.register_function("values", 1, values);
+    interpreter.register_function("has", 2, has);
+}
+
+fn write(output: &Output, arguments: &[Value], end: &str) -> Result<Value, CustomError> {
+    let text = arguments.iter().map(|argument| argument._to_string()).collect::<Vec<String>>().join(" ");
+    let mut output = output.borrow_mut();
+    match write!(output, "{}{}", text, end).and_then(|_| output.flush()) {
+        Ok(_) => Ok(Value::new_null()),
+        Err(e) => Err(CustomError::new_io_error(&e.to_string())),
+    }
+}
+
+fn len(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::String(s) => Ok(Value::new_integer(s.chars().count() as i64)),
+        Value::List(list) => Ok(Value::new_integer(list.borrow().len() as i64)),
+        Value::Map(map) => Ok(Value::new_integer(map.borrow().len() as i64)),
+        Value::Range(start, end) => Ok(Value::new_integer(end.saturating_sub(*start).max(0))),
+        value => Err(CustomError::new_type_error(&format!("Cannot get the length of {:?}", value))),
+    }
+}
+
+/// Keys of a map, in insertion order.
+fn keys(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Map(map) => Ok(Value::new_list(map.borrow().iter().map(|(key, _)| key.clone()).collect())),
+        value => Err(CustomError::new_type_error(&format!("Cannot get the keys of {:?}", value))),
+    }
+}
+
+/// Values of a map, in the order of their keys.
+fn values(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Map(map) => Ok(Value::new_list(map.borrow().iter().map(|(_, value)| value.clone()).collect())),
+        value => Err(CustomError::new_type_error(&format!("Cannot get the values of {:?}", value))),
+    }
+}
+
+fn has(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Map(map) => Ok(Value::new_boolean(map.borrow().find(&arguments[1])?.is_some())),
+        value => Err(CustomError::new_type_error(&format!("Cannot look up a key in {:?}", value))),
+    }
+}
+
+fn int(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Integer(i) => Ok(Value::new_integer(*i)),
+        Value::Float(f) => {
+            // NaN fails both comparisons
+            if f.trunc() >= -9223372036854775808.0 && f.trunc() < 9223372036854775808.0 {
+                Ok(Value::new_integer(*f as i64))
+            } else {
+                Err(CustomError::new_conversion_error(&format!("{:?}", arguments[0]), "integer"))
+            }
+        },
+        Value::String(s) => match s.trim().parse::<i64>() {
+            Ok(i) => Ok(Value::new_integer(i)),
+            Err(_) => Err(CustomError::new_conversion_error(&format!("{:?}", arguments[0]), "integer")),
+        },
+        Value::Boolean(b) => Ok(Value::new_integer(*b as i64)),
+        value => Err(CustomError::new_conversion_error(&format!("{:?}", value), "integer")),
+    }
+}
+
+fn float(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Integer(i) => Ok(Value::new_float(*i as f64)),
+        Value::Float(f) => Ok(Value::new_float(*f)),
+        Value::String(s) => match s.trim().parse::<f64>() {
+            Ok(f) => Ok(Value::new_float(f)),
+            Err(_) => Err(CustomError::new_conversion_error(&format!("{:?}", arguments[0]), "float")),
+        },
+        Value::Boolean(b) => Ok(Value::new_float(if *b { 1.0 } else { 0.0 })),
+        value => Err(CustomError::new_conversion_error(&format!("{:?}", value), "float")),
+    }
+}
+
+fn bool(arguments: &[Value]) -> Result<Value, CustomError> {
+    match &arguments[0] {
+        Value::Integer(i) => Ok(Value::new_boolean(*i != 0)),
+        Value::Float(f) => Ok(Value::new_boolean(*f != 0.0)),
+        Value::String(s) => Ok(Value::new_boolean(!s.is_empty())),
+        Value::Boolean(b) => Ok(Value::new_boolean(*b)),
+        Value::Null() => Ok(Value::new_boolean(false)),
+        Value::Function(_) => Ok(Value::new_boolean(true)),
+        Value::List(list) => Ok(Value::new_boolean(!list.borrow().is_empty())),
+        Value::Map(map) => Ok(Value::new_boolean(!map.borrow().is_empty())),
+        Value::Range(start, end) => Ok(Value::new_boolean(start < end)),
+    }
+}
@@ -0,0 +1,471 @@
+use std::rc::Rc;
+use crate::ast::{Block, Expr, FunctionDef, Slot, Stmt};
+use crate::span::Span;
+use crate::stack;
+use crate::value::Value;
+
+/// Instructions of the stack machine run by `vm::Vm`, indexes pointing into the pools of their `Chunk`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpCode {
+    /// Pushes `constants[i]`.
+    Constant(usize),
+    /// Pushes the value of the variable `variables[i]`.
+    Load(usize),
+    /// Pops a value into the variable `variables[i]`.
+    Store(usize),
+    Pop,
+    /// Pops two operands and pushes the result of the arithmetic `operator`.
+    Binary(char),
+    Unary(char),
+    Compare(char),
+    /// Pops the left operand of `and` (`&`) / `or` (`|`): when it decides the result, pushes it back and jumps.
+    ShortCircuit(char, usize),
+    /// Fails unless the top of the stack, the right operand of `and` / `or`, is a boolean.
+    CheckBoolean,
+    /// Fails unless the top of the stack is a function, before its arguments are evaluated.
+    CheckFunction,
+    Jump(usize),
+    /// Pops a condition and jumps unless it is `true`.
+    JumpIfFalse(usize),
+    EnterScope,
+    ExitScope,
+    /// Pops a value and starts iterating over it for a `for` loop with `n` variables.
+    Iterate(usize),
+    /// Pushes the `n` variables of the next iteration of the innermost loop, or jumps when the loop is over.
+    Next(usize, usize),
+    /// Ends the innermost loop started by `Iterate`.
+    DropIterator,
+    /// Pops a value into the variable `i` of the innermost scope.
+    Declare(usize),
+    /// Declares `functions[i]` in the variable `variables[j]`.
+    Function(usize, usize),
+    /// Pops the arguments then the function, and pushes the returned value.
+    Call(usize),
+    /// Pops the returned value and leaves the chunk.
+    Return,
+    /// Pops the end then the start of a range, and pushes the range.
+    Range,
+    /// Pops `n` elements and pushes a new list holding them.
+    List(usize),
+    /// Pops `n` key and value pairs and pushes a new map holding them.
+    Map(usize),
+    /// Pops an index then the indexed value, and pushes the element.
+    Index,
+    /// Pops the end and start bounds then the sliced value, and pushes the slice.
+    Slice,
+    /// Pops the value, the index then the indexed value, and stores the value at the index.
+    SetIndex,
+    /// Starts a `try` block: until the matching `EndTry`, an error unwinds the stack, loops and scopes to their state at
+    /// this point and jumps to `target`, where it waits to be caught.
+    Try(usize),
+    /// Ends the innermost `try` block.
+    EndTry,
+    /// Pushes the value of the error waiting to be caught, as given to a `catch` handler.
+    Catch,
+    /// Raises the error waiting to be caught again, at the end of the `finally` block run for it.
+    Rethrow,
+    /// Pops a value and raises it.
+    Throw,
+}
+
+/// Compiled code, `spans[i]` being the source of `code[i]` for error reporting.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    code: Vec<OpCode>,
+    spans: Vec<Option<Span>>,
+    constants: Vec<Value>,
+    variables: Vec<(String, Slot)>,
+    functions: Vec<Rc<FunctionDef>>,
+}
+
+impl Chunk {
+    pub fn get_code(&self) -> &[OpCode] {
+        &self.code
+    }
+
+    pub fn get_span(&self, ip: usize) -> Option<Span> {
+        self.spans[ip]
+    }
+
+    pub fn get_constant(&self, i: usize) -> &Value {
+        &self.constants[i]
+    }
+
+    /// Name of the variable `i` and where it is stored.
+    pub fn get_variable(&self, i: usize) -> (&str, &Slot) {
+        let (name, slot) = &self.variables[i];
+        (name, slot)
+    }
+
+    pub fn get_function(&self, i: usize) -> &Rc<FunctionDef> {
+        &self.functions[i]
+    }
+}
+
+/// A loop being compiled, the target of the `break` and `continue` statements of its body.
+struct Loop {
+    /// Scopes open around the loop, the ones opened since being exited before jumping out of the body.
+    scopes: usize,
+    /// Cleanups around the loop, the ones pushed since being undone before jumping out of the body.
+    cleanups: usize,
+    /// Where `continue` jumps to.
+    start: usize,
+    /// Jumps of the `break` statements, pointed at the end of the loop once it is compiled.
+    breaks: Vec<usize>,
+}
+
+/// What leaving a `try` statement early, through `return`, `break` or `continue`, must undo first.
+#[derive(Clone)]
+enum Cleanup {
+    /// Inside a `try` block, or a `catch` handler followed by a `finally` block: the handler of the block is removed and
+    /// the `finally` block run in the `scopes` and `loops` around the statement.
+    Try { scopes: usize, loops: usize, finally: Option<Block> },
+    /// Inside the `finally` block run for an error, which is dropped.
+    PendingError,
+}
+
+pub struct Compiler {
+    chunk: Chunk,
+    /// Scopes opened by the code compiled so far and not yet exited.
+    scopes: usize,
+    loops: Vec<Loop>,
+    cleanups: Vec<Cleanup>,
+}
+
+impl Compiler {
+    /// Compiles a single top-level statement, an expression statement leaving its value on the stack.
+    pub fn compile_instruction(stmt: &Stmt) -> Chunk {
+        let mut compiler = Compiler{chunk: Chunk::default(), scopes: 0, loops: Vec::new(), cleanups: Vec::new()};
+        match stmt {
+            Stmt::Expr(expr) => compiler.compile_expr(expr),
+            stmt => compiler.compile_stmt(stmt),
+        }
+        compiler.chunk
+    }
+
+    /// Compiles the body of a function, run once the parameters are declared.
+    pub fn compile_function(definition: &FunctionDef) -> Chunk {
+        let mut compiler = Compiler{chunk: Chunk::default(), scopes: 0, loops: Vec::new(), cleanups: Vec::new()};
+        compiler.compile_block(&definition.body);
+        compiler.chunk
+    }
+
+    fn emit(&mut self, op: OpCode, span: Option<Span>) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push(span);
+        self.chunk.code.len() - 1
+    }
+
+    /// Points the jump at `position` to the next instruction to be emitted.
+    fn patch_jump(&mut self, position: usize) {
+        let target = self.chunk.code.len();
+        self.chunk.code[position] = match self.chunk.code[position] {
+            OpCode::Jump(_) => OpCode::Jump(target),
+            OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(target),
+            OpCode::ShortCircuit(operator, _) => OpCode::ShortCircuit(operator, target),
+            OpCode::Next(count, _) => OpCode::Next(count, target),
+            OpCode::Try(_) => OpCode::Try(target),
+            op => op,
+        };
+    }
+
+    fn add_constant(&mut self, value: Value) -> usize {
+        self.chunk.constants.push(value);
+        self.chunk.constants.len() - 1
+    }
+
+    fn add_variable(&mut self, name: &str, slot: &Slot) -> usize {
+        self.chunk.variables.push((name.to_string(), slot.clone()));
+        self.chunk.variables.len() - 1
+    }
+
+    fn compile_block(&mut self, block: &Block) {
+        stack::guard(|| self.compile_block_in_place(block))
+    }
+
+    fn compile_block_in_place(&mut self, block: &Block) {
+        self.enter_scope();
+        for stmt in block {
+            self.compile_stmt(stmt);
+        }
+        self.exit_scope();
+    }
+
+    fn enter_scope(&mut self) {
+        self.emit(OpCode::EnterScope, None);
+        self.scopes += 1;
+    }
+
+    fn exit_scope(&mut self) {
+        self.emit(OpCode::ExitScope, None);
+        self.scopes -= 1;
+    }
+
+    /// Compiles the `body` of a loop starting at `start`, then points its `break` statements at the code that follows.
+    fn compile_loop<F: FnOnce(&mut Compiler)>(&mut self, start: usize, body: F) {
+        self.loops.push(Loop{scopes: self.scopes, cleanups: self.cleanups.len(), start, breaks: Vec::new()});
+        body(self);
+        let exited = self.loops.pop().expect("the loop was pushed above");
+        for position in exited.breaks {
+            self.patch_jump(position);
+        }
+    }
+
+    /// Exits the scopes opened inside the innermost loop, then leaves it for `break` or starts its next iteration for
+    /// `continue`. Outside of any loop, which only statements built without the parser allow, returns `null` instead.
+    fn compile_loop_exit(&mut self, is_break: bool) {
+        let (scopes, cleanups, start) = match self.loops.last() {
+            Some(innermost) => (innermost.scopes, innermost.cleanups, innermost.start),
+            None => {
+                self.compile_constant(Value::new_null());
+                self.compile_unwind(0);
+                self.emit(OpCode::Return, None);
+                return;
+            },
+        };
+        let open = self.compile_unwind(cleanups);
+        for _ in scopes..open {
+            self.emit(OpCode::ExitScope, None);
+        }
+        if is_break {
+            let position = self.emit(OpCode::Jump(0), None);
+            self.loops.last_mut().expect("checked above").breaks.push(position);
+        } else {
+            self.emit(OpCode::Jump(start), None);
+        }
+    }
+
+    /// Undoes the cleanups from `depth` on, innermost first, and returns the number of scopes left open.
+    fn compile_unwind(&mut self, depth: usize) -> usize {
+        let mut open = self.scopes;
+        for i in (depth..self.cleanups.len()).rev() {
+            match self.cleanups[i].clone() {
+                Cleanup::PendingError => {
+                    self.emit(OpCode::Catch, None);
+                    self.emit(OpCode::Pop, None);
+                },
+                Cleanup::Try{scopes, loops, finally} => {
+                    for _ in scopes..open {
+                        self.emit(OpCode::ExitScope, None);
+                    }
+                    open = scopes;
+                    self.emit(OpCode::EndTry, None);
+                    if let Some(finally) = finally {
+                        // The block is compiled as if it followed the `try` statement, outside of its cleanups and loops.
+                        let cleanups = self.cleanups.split_off(i);
+                        let inner_loops = self.loops.split_off(loops);
+                        let inner_scopes = std::mem::replace(&mut self.scopes, scopes);
+                        self.compile_block(&finally);
+                        self.scopes = inner_scopes;
+                        self.loops.extend(inner_loops);
+                        self.cleanups.extend(cleanups);
+                    }
+                },
+            }
+        }
+        open
+    }
+
+    /// Compiles a `try` statement, the `finally` block being compiled twice: after `body` and the handler end, and for
+    /// an error they raise, which is raised again once the block is over.
+    fn compile_try(&mut self, body: &Block, catch: &Option<(String, Block)>, finally: &Option<Block>) {
+        if catch.is_none() && finally.is_none() {
+            return self.compile_block(body);
+        }
+        let mut to_handler = self.emit(OpCode::Try(0), None);
+        self.cleanups.push(Cleanup::Try{scopes: self.scopes, loops: self.loops.len(), finally: finally.clone()});
+        self.compile_block(body);
+        self.cleanups.pop();
+        self.emit(OpCode::EndTry, None);
+        if let Some((_, handler)) = catch {
+            let to_finally = self.emit(OpCode::Jump(0), None);
+            self.patch_jump(to_handler);
+            if finally.is_some() {
+                to_handler = self.emit(OpCode::Try(0), None);
+                self.cleanups.push(Cleanup::Try{scopes: self.scopes, loops: self.loops.len(), finally: finally.clone()});
+            }
+            self.emit(OpCode::Catch, None);
+            self.enter_scope();
+            self.emit(OpCode::Declare(0), None);
+            self.compile_block(handler);
+            self.exit_scope();
+            if finally.is_some() {
+                self.cleanups.pop();
+                self.emit(OpCode::EndTry, None);
+            }
+            self.patch_jump(to_finally);
+        }
+        if let Some(finally) = finally {
+            self.compile_block(finally);
+            let to_end = self.emit(OpCode::Jump(0), None);
+            self.patch_jump(to_handler);
+            self.cleanups.push(Cleanup::PendingError);
+            self.compile_block(finally);
+            self.cleanups.pop();
+            self.emit(OpCode::Rethrow, None);
+            self.patch_jump(to_end);
+        }
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.compile_expr(expr);
+                self.emit(OpCode::Pop, None);
+            },
+            Stmt::Assign{name, value, span, slot} => {
+                self.compile_expr(value);
+                let variable = self.add_variable(name, slot);
+                self.emit(OpCode::Store(variable), *span);
+            },
+            Stmt::SetIndex{target, index, value, span} => {
+                self.compile_expr(target);
+                self.compile_expr(index);
+                self.compile_expr(value);
+                self.emit(OpCode::SetIndex, *span);
+            },
+            Stmt::Block(block) => self.compile_block(block),
+            Stmt::If{condition, then_block, else_branch} => {
+                self.compile_expr(condition);
+                let to_else = self.emit(OpCode::JumpIfFalse(0), None);
+                self.compile_block(then_block);
+                let to_end = self.emit(OpCode::Jump(0), None);
+                self.patch_jump(to_else);
+                if let Some(else_branch) = else_branch {
+                    self.compile_stmt(else_branch);
+                }
+                self.patch_jump(to_end);
+            },
+            Stmt::While{condition, body} => {
+                let start = self.chunk.code.len();
+                self.compile_loop(start, |compiler| {
+                    compiler.compile_expr(condition);
+                    let to_end = compiler.emit(OpCode::JumpIfFalse(0), None);
+                    compiler.compile_block(body);
+                    compiler.emit(OpCode::Jump(start), None);
+                    compiler.patch_jump(to_end);
+                });
+            },
+            Stmt::For{variables, iterable, body, span} => {
+                self.compile_expr(iterable);
+                self.emit(OpCode::Iterate(variables.len()), *span);
+                let start = self.chunk.code.len();
+                // `break` jumps to `DropIterator`, like the end of the iteration.
+                self.compile_loop(start, |compiler| {
+                    let to_end = compiler.emit(OpCode::Next(variables.len(), 0), None);
+                    compiler.enter_scope();
+                    for i in (0..variables.len()).rev() {
+                        compiler.emit(OpCode::Declare(i), None);
+                    }
+                    compiler.compile_block(body);
+                    compiler.exit_scope();
+                    compiler.emit(OpCode::Jump(start), None);
+                    compiler.patch_jump(to_end);
+                });
+                self.emit(OpCode::DropIterator, None);
+            },
+            Stmt::Function(definition, slot) => {
+                self.chunk.functions.push(Rc::clone(definition));
+                let variable = self.add_variable(&definition.name, slot);
+                self.emit(OpCode::Function(self.chunk.functions.len() - 1, variable), definition.span);
+            },
+            Stmt::Return{value, span} => {
+                self.compile_expr(value);
+                self.compile_unwind(0);
+                self.emit(OpCode::Return, *span);
+            },
+            Stmt::Break => self.compile_loop_exit(true),
+            Stmt::Continue => self.compile_loop_exit(false),
+            Stmt::Throw{value, span} => {
+                self.compile_expr(value);
+                self.emit(OpCode::Throw, *span);
+            },
+            Stmt::Try{body, catch, finally} => self.compile_try(body, catch, finally),
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        stack::guard(|| self.compile_expr_in_place(expr))
+    }
+
+    fn compile_expr_in_place(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Integer(value) => self.compile_constant(Value::new_integer(*value)),
+            Expr::Float(value) => self.compile_constant(Value::new_float(*value)),
+            Expr::String(value) => self.compile_constant(Value::new_string(value)),
+            Expr::Boolean(value) => self.compile_constant(Value::new_boolean(*value)),
+            Expr::Null => self.compile_constant(Value::new_null()),
+            Expr::Variable{name, span, slot} => {
+                let variable = self.add_variable(name, slot);
+                self.emit(OpCode::Load(variable), *span);
+            },
+            Expr::Binary{left, operator, right, span} => {
+                self.compile_expr(left);
+                self.compile_expr(right);
+                self.emit(OpCode::Binary(*operator), *span);
+            },
+            Expr::Unary{operator, operand, span} => {
+                self.compile_expr(operand);
+                self.emit(OpCode::Unary(*operator), *span);
+            },
+            Expr::Range{start, end, span} => {
+                self.compile_expr(start);
+                self.compile_expr(end);
+                self.emit(OpCode::Range, *span);
+            },
+            Expr::Comparison{left, operator, right, span} => {
+                self.compile_expr(left);
+                self.compile_expr(right);
+                self.emit(OpCode::Compare(*operator), *span);
+            },
+            Expr::Logical{left, operator, right, span} => {
+                self.compile_expr(left);
+                let to_end = self.emit(OpCode::ShortCircuit(*operator, 0), *span);
+                self.compile_expr(right);
+                self.emit(OpCode::CheckBoolean, *span);
+                self.patch_jump(to_end);
+            },
+            Expr::Call{callee, arguments, span} => {
+                self.compile_expr(callee);
+                self.emit(OpCode::CheckFunction, *span);
+                for argument in arguments {
+                    self.compile_expr(argument);
+                }
+                self.emit(OpCode::Call(arguments.len()), *span);
+            },
+            Expr::List{elements, span} => {
+                for element in elements {
+                    self.compile_expr(element);
+                }
+                self.emit(OpCode::List(elements.len()), *span);
+            },
+            Expr::Map{entries, span} => {
+                for (key, value) in entries {
+                    self.compile_expr(key);
+                    self.compile_expr(value);
+                }
+                self.emit(OpCode::Map(entries.len()), *span);
+            },
+            Expr::Index{target, index, span} => {
+                self.compile_expr(target);
+                self.compile_expr(index);
+                self.emit(OpCode::Index, *span);
+            },
+            Expr::Slice{target, start, end, span} => {
+                self.compile_expr(target);
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.compile_expr(bound),
+                        None => self.compile_constant(Value::new_null()),
+                    }
+                }
+                self.emit(OpCode::Slice, *span);
+            },
+        }
+    }
+
+    fn compile_constant(&mut self, value: Value) {
+        let constant = self.add_constant(value);
+        self.emit(OpCode::Constant(constant), None);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::errors::CustomError;
+use crate::span::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders errors as diagnostics quoting the source they point at:
+///
+/// ```text
+/// error[E0005]: OperationError: Cannot add Integer(1) and String(a)
+///  --> main.krab:2:5
+///   |
+/// 2 | b = a + "a";
+///   |     ^^^^^^^
+/// ```
+pub struct Renderer {
+    colored: bool,
+}
+
+impl Renderer {
+    /// `colored` adds ANSI escape codes, meant for terminals; plain text is used otherwise.
+    pub fn new(colored: bool) -> Renderer {
+        Renderer{colored}
+    }
+
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.colored {
+            return format!("{}{}{}", color, text, RESET);
+        }
+        return text.to_string();
+    }
+
+    /// Renders `error` raised while running `source`, read from `file`.
+    pub fn render(&self, error: &CustomError, source: &str, file: &str) -> String {
+        let mut output = format!("{}{}\n",
+            self.paint(&format!("error[{}]", error.get_code()), RED),
+            self.paint(&format!(": {}: {}", error.get_error_type(), error.get_message()), BOLD));
+        // Lines and columns start at 1, a span at 0 not pointing anywhere in the source.
+        let span = match error.get_span() {
+            Some(span) if span.get_line() > 0 && span.get_column() > 0 => *span,
+            _ => {
+                for note in error.get_notes() {
+                    output.push_str(&format!("= note: {}\n", note));
+                }
+                return output;
+            }
+        };
+        let mut labels: Vec<(&Span, &str, bool)> = vec![(&span, "", true)];
+        for (label_span, message) in error.get_labels() {
+            if label_span.get_line() > 0 && label_span.get_column() > 0 {
+                labels.push((label_span, message, false));
+            }
+        }
+        labels.sort_by_key(|(label_span, _, _)| (label_span.get_line(), label_span.get_column()));
+        let width = labels.iter().map(|(label_span, _, _)| label_span.get_line()).max().unwrap_or(1).to_string().len();
+        let gutter = self.paint(&format!("{} |", " ".repeat(width)), BLUE);
+        output.push_str(&format!("{}{} {}:{}\n", " ".repeat(width), self.paint("-->", BLUE), file, span));
+        output.push_str(&format!("{}\n", gutter));
+        let lines: Vec<&str> = source.lines().collect();
+        let mut previous_line = 0;
+        for (label_span, message, primary) in labels {
+            let line = label_span.get_line();
+            let text = line.checked_sub(1).and_then(|index| lines.get(index)).copied().unwrap_or("");
+            if line != previous_line {
+                output.push_str(&format!("{} {}\n", self.paint(&format!("{:>width$} |", line, width = width), BLUE), text));
+                previous_line = line;
+            }
+            let (marker, color) = if primary { ('^', RED) } else { ('-', BLUE) };
+            let underline = marker.to_string().repeat(Self::get_underline_length(label_span, source, text));
+            let mut annotation = format!("{}{}", " ".repeat(label_span.get_column().saturating_sub(1)), underline);
+            if !message.is_empty() {
+                annotation = format!("{} {}", annotation, message);
+            }
+            output.push_str(&format!("{} {}\n", gutter, self.paint(&annotation, color)));
+        }
+        if !error.get_notes().is_empty() {
+            output.push_str(&format!("{}\n", gutter));
+        }
+        for note in error.get_notes() {
+            output.push_str(&format!("{} {} {}\n", " ".repeat(width), self.paint("= note:", BLUE), note));
+        }
+        return output;
+    }
+
+    /// Number of characters of `span` on its first line, at least one so that empty spans stay visible.
+    fn get_underline_length(span: &Span, source: &str, line: &str) -> usize {
+        let covered = source.get(span.get_start()..span.get_end()).unwrap_or("");
+        let available = line.chars().count().saturating_sub(span.get_column().saturating_sub(1));
+        let length = covered.chars().take_while(|c| *c != '\n').count().min(available);
+        return length.max(1);
+    }
+}
@@ -1,50 +1,217 @@
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display};
+use crate::span::Span;
+use crate::value::Value;
+
+/// What went wrong, with the data needed to act on it without parsing the message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    VariableNotFound { name: String },
+    OperatorNotFound { operator: char },
+    Operation,
+    DivisionByZero,
+    IntegerOverflow,
+    RecursionLimit { limit: usize },
+    Index { index: i64, length: usize },
+    Key { key: String },
+    /// Raised by a `throw` statement, the thrown value being kept by the error.
+    Thrown,
+    TypeError,
+    Conversion { value: String, target: String },
+    IO,
+    Lexer,
+    Parser,
+    FileNotFound { path: String },
+}
+
+impl ErrorKind {
+    /// Name shown in front of the message, such as `ParserError`.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ErrorKind::VariableNotFound { .. } => "VariableNotFoundError",
+            ErrorKind::OperatorNotFound { .. } => "OperatorNotFoundError",
+            ErrorKind::Operation => "OperationError",
+            ErrorKind::DivisionByZero => "DivisionByZeroError",
+            ErrorKind::IntegerOverflow => "IntegerOverflowError",
+            ErrorKind::RecursionLimit { .. } => "RecursionLimitError",
+            ErrorKind::Index { .. } => "IndexError",
+            ErrorKind::Key { .. } => "KeyError",
+            ErrorKind::Thrown => "ThrownError",
+            ErrorKind::TypeError => "TypeError",
+            ErrorKind::Conversion { .. } => "ConversionError",
+            ErrorKind::IO => "IOError",
+            ErrorKind::Lexer => "LexerError",
+            ErrorKind::Parser => "ParserError",
+            ErrorKind::FileNotFound { .. } => "FileNotFoundError",
+        }
+    }
+
+    /// Stable code identifying the kind, shown in diagnostics.
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            ErrorKind::Lexer => "E0001",
+            ErrorKind::Parser => "E0002",
+            ErrorKind::VariableNotFound { .. } => "E0003",
+            ErrorKind::OperatorNotFound { .. } => "E0004",
+            ErrorKind::Operation => "E0005",
+            ErrorKind::TypeError => "E0006",
+            ErrorKind::Conversion { .. } => "E0007",
+            ErrorKind::IO => "E0008",
+            ErrorKind::FileNotFound { .. } => "E0009",
+            ErrorKind::DivisionByZero => "E0010",
+            ErrorKind::IntegerOverflow => "E0011",
+            ErrorKind::RecursionLimit { .. } => "E0012",
+            ErrorKind::Index { .. } => "E0013",
+            ErrorKind::Key { .. } => "E0014",
+            ErrorKind::Thrown => "E0015",
+        }
+    }
+}
 
+/// An error, its details being boxed to keep the `Result`s carrying it small.
 pub struct CustomError {
+    data: Box<ErrorData>,
+}
+
+struct ErrorData {
     message: String,
-    error_type: String,
+    kind: ErrorKind,
+    span: Option<Span>,
+    labels: Vec<(Span, String)>,
+    notes: Vec<String>,
+    thrown: Option<Value>,
 }
 
 
 impl CustomError {
-    pub fn new(message: &str, error_type: &str) -> CustomError {
-        CustomError{message: message.to_string(), error_type: error_type.to_string()}
+    pub fn new(message: &str, kind: ErrorKind) -> CustomError {
+        CustomError{data: Box::new(ErrorData{message: message.to_string(), kind, span: None, labels: Vec::new(), notes: Vec::new(), thrown: None})}
+    }
+
+    /// Points the error at `span`, unless it already points at a more precise location.
+    pub fn with_span(mut self, span: Span) -> CustomError {
+        if self.data.span.is_none() {
+            self.data.span = Some(span);
+        }
+        self
+    }
+
+    pub fn get_span(&self) -> Option<&Span> {
+        self.data.span.as_ref()
+    }
+
+    /// Attaches a secondary location to the error, such as where an unclosed block started.
+    pub fn with_label(mut self, span: Span, message: &str) -> CustomError {
+        self.data.labels.push((span, message.to_string()));
+        self
+    }
+
+    pub fn get_labels(&self) -> &Vec<(Span, String)> {
+        &self.data.labels
+    }
+
+    pub fn with_note(mut self, note: &str) -> CustomError {
+        self.data.notes.push(note.to_string());
+        self
+    }
+
+    pub fn get_notes(&self) -> &Vec<String> {
+        &self.data.notes
+    }
+
+    pub fn get_code(&self) -> &'static str {
+        self.data.kind.get_code()
     }
 
     pub fn new_variable_not_found_error(variable_name: &str) -> CustomError {
-        CustomError::new(&format!("Variable {} does not exist", variable_name), "VariableNotFoundError")
+        CustomError::new(&format!("Variable {} does not exist", variable_name), ErrorKind::VariableNotFound{name: variable_name.to_string()})
     }
 
     pub fn new_operator_not_found_error(operator: char) -> CustomError {
-        CustomError::new(&format!("Operator {} not found", operator), "OperatorNotFoundError")
+        CustomError::new(&format!("Operator {} not found", operator), ErrorKind::OperatorNotFound{operator})
     }
 
     pub fn new_operation_error(message: &str) -> CustomError {
-        CustomError::new(message, "OperationError")
+        CustomError::new(message, ErrorKind::Operation)
     }
 
-    pub fn new_lexer_error(message: &str) -> CustomError {CustomError::new(message, "LexerError")
+    pub fn new_division_by_zero_error() -> CustomError {
+        CustomError::new("Division by zero", ErrorKind::DivisionByZero)
     }
 
-    pub fn new_parser_error(message: &str) -> CustomError{CustomError::new(message, "ParserError")}
+    pub fn new_integer_overflow_error() -> CustomError {
+        CustomError::new("Integer overflow", ErrorKind::IntegerOverflow)
+    }
+
+    pub fn new_recursion_limit_error(limit: usize) -> CustomError {
+        CustomError::new(&format!("Maximum recursion depth of {} exceeded", limit), ErrorKind::RecursionLimit{limit})
+    }
+
+    pub fn new_index_error(index: i64, length: usize) -> CustomError {
+        CustomError::new(&format!("Index {} out of range for a list of length {}", index, length), ErrorKind::Index{index, length})
+    }
+
+    /// `key` is the missing key as printed in Krab, e.g. `"name"`.
+    pub fn new_key_error(key: &str) -> CustomError {
+        CustomError::new(&format!("Key {} not found", key), ErrorKind::Key{key: key.to_string()})
+    }
+
+    /// The error raised by `throw value`, its message being the value as printed by `print`.
+    pub fn new_thrown_error(value: Value) -> CustomError {
+        let mut error = CustomError::new(&value._to_string(), ErrorKind::Thrown);
+        error.data.thrown = Some(value);
+        error
+    }
+
+    pub fn new_type_error(message: &str) -> CustomError {
+        CustomError::new(message, ErrorKind::TypeError)
+    }
+
+    pub fn new_conversion_error(value: &str, target: &str) -> CustomError {
+        CustomError::new(&format!("Cannot convert {} to {}", value, target), ErrorKind::Conversion{value: value.to_string(), target: target.to_string()})
+    }
+
+    pub fn new_io_error(message: &str) -> CustomError {
+        CustomError::new(message, ErrorKind::IO)
+    }
+
+    pub fn new_lexer_error(message: &str) -> CustomError {CustomError::new(message, ErrorKind::Lexer)
+    }
+
+    pub fn new_parser_error(message: &str) -> CustomError{CustomError::new(message, ErrorKind::Parser)}
 
 
     pub fn new_file_not_found_error(file_path: &str) -> CustomError{
-        CustomError::new(&format!("File {} not found", file_path), "FileNotFoundError")
+        CustomError::new(&format!("File {} not found", file_path), ErrorKind::FileNotFound{path: file_path.to_string()})
     }
 
     pub fn get_message(&self) -> &str {
-        &self.message
+        &self.data.message
+    }
+
+    pub fn get_kind(&self) -> &ErrorKind {
+        &self.data.kind
     }
 
     pub fn get_error_type(&self) -> &str {
-        &self.error_type
+        self.data.kind.get_name()
+    }
+
+    /// Value stored in the variable of a `catch` handler: the thrown value, or for an error raised by the interpreter a
+    /// map holding its `kind`, such as `"KeyError"`, and its `message`.
+    pub fn into_value(self) -> Value {
+        match self.data.thrown {
+            Some(value) => value,
+            None => Value::new_map(vec![
+                (Value::new_string("kind"), Value::new_string(self.data.kind.get_name())),
+                (Value::new_string("message"), Value::new_string(&self.data.message)),
+            ]).expect("string keys are compared without error"),
+        }
     }
 
     pub fn _equals(&self, error: &CustomError) -> bool {
-        self.message == error.message && self.error_type == error.error_type
+        self.data.message == error.data.message && self.data.kind == error.data.kind
     }
 
 }
@@ -57,9 +224,8 @@ impl Display for CustomError {
 
 impl Debug for CustomError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "CustomError: {}", self.message)
+        write!(f, "CustomError: {}", self.data.message)
     }
 }
 
 impl Error for CustomError {}
-
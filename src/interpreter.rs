@@ -1,11 +1,52 @@
-use std::ops::Deref;
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::cell::{OnceCell, RefCell};
+use std::io::{self, Write};
+use crate::ast::{Block, Expr, FunctionDef, Slot, Stmt};
+use crate::builtins::{register_builtins, Output};
+use crate::compiler::{Chunk, Compiler};
+use crate::resolver::Resolver;
+use crate::runtime::{get_boolean, is_true, locate};
+use crate::stack;
 use crate::errors::CustomError;
+use crate::span::Span;
 use crate::value::Value;
 use crate::variables::VariableManager;
+use crate::vm;
 
 
+/// Builds an expression of the syntax tree, the nodes below being a convenient way to write one by hand.
 pub trait Valuable {
-    fn get_value(&self, variables: &mut VariableManager) -> Result<Value, CustomError>;
+    fn to_expr(&self) -> Expr;
+}
+
+/// Anything the interpreter can run: a statement of the syntax tree, or a node building one.
+pub trait Instruction {
+    fn to_stmt(&self) -> Cow<'_, Stmt>;
+}
+
+impl<T: Valuable + ?Sized> Instruction for T {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Expr(self.to_expr()))
+    }
+}
+
+impl Valuable for Box<dyn Valuable> {
+    fn to_expr(&self) -> Expr {
+        self.as_ref().to_expr()
+    }
+}
+
+impl Instruction for Box<dyn Instruction> {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        self.as_ref().to_stmt()
+    }
+}
+
+impl Instruction for Stmt {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Borrowed(self)
+    }
 }
 
 pub struct FloatValue {
@@ -38,322 +79,885 @@ impl StringValue {
     }
 }
 
+pub struct BooleanValue {
+    value: bool,
+}
+
+impl BooleanValue {
+    pub fn new(value: bool) -> BooleanValue {
+        BooleanValue {value}
+    }
+}
+
+pub struct NullValue {}
+
+impl NullValue {
+    pub fn new() -> NullValue {
+        NullValue {}
+    }
+}
+
+impl Default for NullValue {
+    fn default() -> NullValue {
+        NullValue::new()
+    }
+}
+
 pub struct Variable {
     name: String,
+    span: Option<Span>,
 }
 
 impl Variable {
     pub fn new(name: &str) -> Variable {
-        Variable{name: name.to_string()}
+        Variable{name: name.to_string(), span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> Variable {
+        self.span = Some(span);
+        self
     }
 }
 
 
 impl Valuable for FloatValue {
-    fn get_value(&self, _variables: &mut VariableManager) -> Result<Value, CustomError> {
-        Ok(Value::new_float(self.value))
+    fn to_expr(&self) -> Expr {
+        Expr::Float(self.value)
     }
 }
 
 impl Valuable for IntegerValue {
-    fn get_value(&self, _variables: &mut VariableManager) -> Result<Value, CustomError> {
-        Ok(Value::new_integer(self.value))
+    fn to_expr(&self) -> Expr {
+        Expr::Integer(self.value)
     }
 }
 
 impl Valuable for StringValue {
-    fn get_value(&self, _variables: &mut VariableManager) -> Result<Value, CustomError> {
-        Ok(Value::new_string(&self.value))
+    fn to_expr(&self) -> Expr {
+        Expr::String(self.value.clone())
     }
 }
 
-
-impl Valuable for Variable {
-    fn get_value(&self, variables: &mut VariableManager) -> Result<Value, CustomError>  {
-        match variables.get_variable(&self.name) {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
-        }
+impl Valuable for BooleanValue {
+    fn to_expr(&self) -> Expr {
+        Expr::Boolean(self.value)
     }
-
 }
 
+impl Valuable for NullValue {
+    fn to_expr(&self) -> Expr {
+        Expr::Null
+    }
+}
 
+impl Valuable for Variable {
+    fn to_expr(&self) -> Expr {
+        Expr::Variable{name: self.name.clone(), span: self.span, slot: Slot::Global}
+    }
+}
 
 pub struct Operation {
-    left: Box<dyn Valuable>,
-    right: Box<dyn Valuable>,
+    left: Expr,
+    right: Expr,
     operator: char,
+    span: Option<Span>,
 }
 
 impl Operation {
     pub fn new(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> Operation {
-        Operation{left, right, operator}
+        Operation{left: left.to_expr(), right: right.to_expr(), operator, span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> Operation {
+        self.span = Some(span);
+        self
     }
 }
 
 impl Valuable for Operation {
-    fn get_value(&self, variables: &mut VariableManager) -> Result<Value, CustomError>  {
-        match self.left.get_value(variables) {
-            Ok(value) => {
-                match self.right.get_value(variables) {
-                    Ok(value2) => {
-                        match self.operator {
-                            '+' => return value.add(&value2),
-                            '-' => return value.sub(&value2),
-                            '*' => return value.mul(&value2),
-                            '/' => return value.div(&value2),
-                            _ => return Err(CustomError::new_operator_not_found_error(self.operator)),
-                        }
-                    },
-                    Err(e) => Err(e),
-                }
-            },
-            Err(e) => Err(e),
-        }
+    fn to_expr(&self) -> Expr {
+        Expr::Binary{left: Box::new(self.left.clone()), operator: self.operator, right: Box::new(self.right.clone()), span: self.span}
+    }
+}
+
+pub struct UnaryOperation {
+    operand: Expr,
+    operator: char,
+    span: Option<Span>,
+}
+
+impl UnaryOperation {
+    pub fn new(operand: Box<dyn Valuable>, operator: char) -> UnaryOperation {
+        UnaryOperation{operand: operand.to_expr(), operator, span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> UnaryOperation {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Valuable for UnaryOperation {
+    fn to_expr(&self) -> Expr {
+        Expr::Unary{operator: self.operator, operand: Box::new(self.operand.clone()), span: self.span}
+    }
+}
+
+/// `and` / `or` between two booleans, the right operand is only evaluated when it decides the result.
+pub struct LogicalOperation {
+    left: Expr,
+    right: Expr,
+    operator: char,
+    span: Option<Span>,
+}
+
+impl LogicalOperation {
+    pub fn new(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> LogicalOperation {
+        LogicalOperation{left: left.to_expr(), right: right.to_expr(), operator, span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> LogicalOperation {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Valuable for LogicalOperation {
+    fn to_expr(&self) -> Expr {
+        Expr::Logical{left: Box::new(self.left.clone()), operator: self.operator, right: Box::new(self.right.clone()), span: self.span}
     }
 }
 
 pub struct Affectation {
     variable: String,
-    value: Box<dyn Valuable>,
+    value: Expr,
 }
 
 impl Affectation {
     pub fn new(variable: &str, value: Box<dyn Valuable>) -> Affectation {
-        Affectation{variable: variable.to_string(), value}
+        Affectation{variable: variable.to_string(), value: value.to_expr()}
     }
 }
 
-pub trait Instruction {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError>;
+impl Instruction for Affectation {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Assign{name: self.variable.clone(), value: self.value.clone(), span: None, slot: Slot::Global})
+    }
 }
 
-impl Instruction for Affectation {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError> {
-        let value;
-        match self.value.get_value(variables) {
-            Ok(v) => value = v,
-            Err(e) => return Err(e),
-        }
-        variables.set_variable(&self.variable, value);
-        Ok(Value::Null())
+pub struct RangeValue {
+    start: Expr,
+    end: Expr,
+}
+
+impl RangeValue {
+    pub fn new(start: Box<dyn Valuable>, end: Box<dyn Valuable>) -> RangeValue {
+        RangeValue{start: start.to_expr(), end: end.to_expr()}
     }
 }
 
-impl Instruction for Operation {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError> {
-        match self.get_value(variables) {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
-        }
+impl Valuable for RangeValue {
+    fn to_expr(&self) -> Expr {
+        Expr::Range{start: Box::new(self.start.clone()), end: Box::new(self.end.clone()), span: None}
     }
 }
 
-impl Instruction for Variable {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError> {
-        match self.get_value(variables) {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
-        }
+pub struct ListValue {
+    elements: Vec<Expr>,
+}
+
+impl ListValue {
+    pub fn new(elements: Vec<Box<dyn Valuable>>) -> ListValue {
+        ListValue{elements: elements.iter().map(|element| element.to_expr()).collect()}
     }
 }
 
-pub struct Condition{
-    left: Box<dyn Valuable>,
-    right: Box<dyn Valuable>,
-    operator: char,
+impl Valuable for ListValue {
+    fn to_expr(&self) -> Expr {
+        Expr::List{elements: self.elements.clone(), span: None}
+    }
 }
 
-impl Instruction for Box<dyn Valuable> {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError> {
-        match self.get_value(variables) {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
-        }
+pub struct MapValue {
+    entries: Vec<(Expr, Expr)>,
+}
+
+impl MapValue {
+    pub fn new(entries: Vec<(Box<dyn Valuable>, Box<dyn Valuable>)>) -> MapValue {
+        MapValue{entries: entries.iter().map(|(key, value)| (key.to_expr(), value.to_expr())).collect()}
+    }
+}
+
+impl Valuable for MapValue {
+    fn to_expr(&self) -> Expr {
+        Expr::Map{entries: self.entries.clone(), span: None}
+    }
+}
+
+pub struct Index {
+    target: Expr,
+    index: Expr,
+    span: Option<Span>,
+}
+
+impl Index {
+    pub fn new(target: Box<dyn Valuable>, index: Box<dyn Valuable>) -> Index {
+        Index{target: target.to_expr(), index: index.to_expr(), span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> Index {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Valuable for Index {
+    fn to_expr(&self) -> Expr {
+        Expr::Index{target: Box::new(self.target.clone()), index: Box::new(self.index.clone()), span: self.span}
+    }
+}
+
+/// `target[index] = value`.
+pub struct IndexAffectation {
+    target: Expr,
+    index: Expr,
+    value: Expr,
+}
+
+impl IndexAffectation {
+    pub fn new(target: Box<dyn Valuable>, index: Box<dyn Valuable>, value: Box<dyn Valuable>) -> IndexAffectation {
+        IndexAffectation{target: target.to_expr(), index: index.to_expr(), value: value.to_expr()}
+    }
+}
+
+impl Instruction for IndexAffectation {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::SetIndex{target: self.target.clone(), index: self.index.clone(), value: self.value.clone(), span: None})
     }
 }
 
+/// How execution continues once an instruction has run.
+pub enum Flow {
+    /// Carry on with the next instruction, holding the value of the instruction.
+    Normal(Value),
+    /// Unwind the enclosing blocks up to the function call, holding the returned value.
+    Return(Value),
+    /// Unwind the enclosing blocks up to the innermost loop, and leave it.
+    Break,
+    /// Unwind the enclosing blocks up to the innermost loop, and start its next iteration.
+    Continue,
+}
+
+pub struct Condition{
+    left: Expr,
+    right: Expr,
+    operator: char,
+    span: Option<Span>,
+}
+
 impl Condition {
     pub fn new(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> Condition {
-        Condition{left, right, operator}
+        Condition{left: left.to_expr(), right: right.to_expr(), operator, span: None}
     }
 
-    pub fn is_true(&self, variables: &mut VariableManager) -> Result<bool, CustomError> {
-        let left; let right;
-        match self.left.get_value(variables) {
-            Ok(value) => left = value,
-            Err(e) => return Err(e),
-        }
-        match self.right.get_value(variables) {
-            Ok(value) => right = value,
-            Err(e) => return Err(e),
+    pub fn with_span(mut self, span: Span) -> Condition {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Valuable for Condition{
+    fn to_expr(&self) -> Expr {
+        Expr::Comparison{left: Box::new(self.left.clone()), operator: self.operator, right: Box::new(self.right.clone()), span: self.span}
+    }
+}
+
+/// Signature of the Rust functions exposed to scripts by the host.
+pub type NativeFunction = dyn Fn(&[Value]) -> Result<Value, CustomError>;
+
+enum FunctionBody {
+    Defined(Rc<FunctionDef>),
+    /// A host function and its number of arguments, `None` when it accepts any number.
+    Native(Option<usize>, Box<NativeFunction>),
+}
+
+pub struct Function {
+    name: String,
+    body: FunctionBody,
+    /// Body compiled by the bytecode engine on the first call.
+    bytecode: OnceCell<Chunk>,
+}
+
+impl Function {
+    pub fn new(name: &str, parameters: Vec<String>, body: InstructionBlock) -> Function {
+        let mut definition = FunctionDef{name: name.to_string(), parameters, body: body.instructions, span: None};
+        Resolver::resolve_function(&mut definition);
+        Function::new_defined(Rc::new(definition))
+    }
+
+    pub fn new_defined(definition: Rc<FunctionDef>) -> Function {
+        Function{name: definition.name.clone(), body: FunctionBody::Defined(definition), bytecode: OnceCell::new()}
+    }
+
+    pub fn new_native(name: &str, arity: Option<usize>, function: Box<NativeFunction>) -> Function {
+        Function{name: name.to_string(), body: FunctionBody::Native(arity, function), bytecode: OnceCell::new()}
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn check_arity(&self, expected: usize, given: usize) -> Result<(), CustomError> {
+        if expected != given {
+            return Err(CustomError::new_operation_error(&format!("Function {} expects {} arguments but {} were given", self.name, expected, given)));
         }
-        let result;
-        match self.operator {
-            '>' => result = left.gt(&right),
-            '<' => result = left.lt(&right),
-            '=' => result = left.eq(&right),
-            '≠' => result = left.neq(&right),
-            _ => return Err(CustomError::new_operator_not_found_error(self.operator)),
+        Ok(())
+    }
+
+    /// Parameters and compiled body of a function declared in Krab, `None` for a native function.
+    pub(crate) fn get_bytecode(&self) -> Option<(&[String], &Chunk)> {
+        match &self.body {
+            FunctionBody::Defined(definition) => Some((&definition.parameters, self.bytecode.get_or_init(|| Compiler::compile_function(definition)))),
+            FunctionBody::Native(..) => None,
         }
-        match result {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
+    }
+
+    /// Calls the function, a defined function runs in a fresh frame where only the parameters and the global variables are visible.
+    pub fn call(&self, arguments: Vec<Value>, variables: &mut VariableManager) -> Result<Value, CustomError> {
+        match &self.body {
+            FunctionBody::Defined(definition) => {
+                self.check_arity(definition.parameters.len(), arguments.len())?;
+                variables.enter_function()?;
+                for (index, argument) in arguments.into_iter().enumerate() {
+                    variables.declare_local(index, argument);
+                }
+                let result = stack::guard(|| execute_block(&definition.body, variables));
+                variables.exit_function();
+                match result? {
+                    Flow::Return(value) => Ok(value),
+                    // A misplaced `break` or `continue`, only built without the parser, ends the function.
+                    Flow::Normal(_) | Flow::Break | Flow::Continue => Ok(Value::new_null()),
+                }
+            },
+            FunctionBody::Native(arity, function) => {
+                if let Some(arity) = arity {
+                    self.check_arity(*arity, arguments.len())?;
+                }
+                function(&arguments)
+            },
         }
     }
 }
 
-impl Valuable for Condition{
-    fn get_value(&self, variables: &mut VariableManager) -> Result<Value, CustomError>  {
-        match self.is_true(variables) {
-            Ok(value) => Ok(Value::new_boolean(value)),
-            Err(e) => Err(e),
+pub struct FunctionDeclaration {
+    definition: Rc<FunctionDef>,
+}
+
+impl FunctionDeclaration {
+    /// Declares a function built with `Function::new`, native functions being registered with `Interpreter::register_function`.
+    pub fn new(function: Function) -> Result<FunctionDeclaration, CustomError> {
+        match function.body {
+            FunctionBody::Defined(definition) => Ok(FunctionDeclaration{definition}),
+            FunctionBody::Native(..) => Err(CustomError::new_type_error(&format!("Native function {} cannot be declared", function.name))),
         }
     }
 }
 
-impl Instruction for Condition {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value,CustomError> {
-        match self.get_value(variables) {
-            Ok(value) => Ok(value),
-            Err(e) => Err(e),
+impl Instruction for FunctionDeclaration {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Function(Rc::clone(&self.definition), Slot::Global))
+    }
+}
+
+pub struct FunctionCall {
+    function: Expr,
+    arguments: Vec<Expr>,
+    span: Option<Span>,
+}
+
+impl FunctionCall {
+    pub fn new(function: Box<dyn Valuable>, arguments: Vec<Box<dyn Valuable>>) -> FunctionCall {
+        FunctionCall{function: function.to_expr(), arguments: arguments.iter().map(|argument| argument.to_expr()).collect(), span: None}
+    }
+
+    pub fn with_span(mut self, span: Span) -> FunctionCall {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Valuable for FunctionCall {
+    fn to_expr(&self) -> Expr {
+        Expr::Call{callee: Box::new(self.function.clone()), arguments: self.arguments.clone(), span: self.span}
+    }
+}
+
+pub struct Return {
+    value: Expr,
+}
+
+impl Return {
+    pub fn new(value: Box<dyn Valuable>) -> Return {
+        Return{value: value.to_expr()}
+    }
+}
+
+impl Instruction for Return {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Return{value: self.value.clone(), span: None})
+    }
+}
+
+/// Leaves the innermost loop.
+pub struct Break;
+
+impl Instruction for Break {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Break)
+    }
+}
+
+/// Moves on to the next iteration of the innermost loop.
+pub struct Continue;
+
+impl Instruction for Continue {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Continue)
+    }
+}
+
+pub struct Throw {
+    value: Expr,
+}
+
+impl Throw {
+    pub fn new(value: Box<dyn Valuable>) -> Throw {
+        Throw{value: value.to_expr()}
+    }
+}
+
+impl Instruction for Throw {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Throw{value: self.value.clone(), span: None})
+    }
+}
+
+pub struct TryBlock {
+    body: Block,
+    catch: Option<(String, Block)>,
+    finally: Option<Block>,
+}
+
+impl TryBlock {
+    /// Runs `handler` with the error stored in `variable` when `body` fails.
+    pub fn new(body: InstructionBlock, variable: &str, handler: InstructionBlock) -> TryBlock {
+        TryBlock{body: body.instructions, catch: Some((variable.to_string(), handler.instructions)), finally: None}
+    }
+
+    pub fn new_with_finally(body: InstructionBlock, variable: &str, handler: InstructionBlock, finally: InstructionBlock) -> TryBlock {
+        TryBlock{body: body.instructions, catch: Some((variable.to_string(), handler.instructions)), finally: Some(finally.instructions)}
+    }
+
+    /// Runs `finally` after `body`, any error of `body` being raised again once `finally` is over.
+    pub fn new_without_catch(body: InstructionBlock, finally: InstructionBlock) -> TryBlock {
+        TryBlock{body: body.instructions, catch: None, finally: Some(finally.instructions)}
+    }
+}
+
+impl Instruction for TryBlock {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Try{body: self.body.clone(), catch: self.catch.clone(), finally: self.finally.clone()})
+    }
+}
+
+/// How the interpreter runs instructions, both engines behaving the same way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    /// Evaluates the syntax tree directly.
+    TreeWalker,
+    /// Compiles each instruction to bytecode run by a stack machine.
+    Bytecode,
+}
+
+impl Engine {
+    /// Engine called `name`, `tree` or `bytecode`.
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "tree" => Some(Engine::TreeWalker),
+            "bytecode" => Some(Engine::Bytecode),
+            _ => None,
         }
     }
 }
 
 pub struct Interpreter {
     pub variables:VariableManager,
+    output: Output,
+    engine: Engine,
 }
 
 pub struct ConditionBlock {
-    conditions: Box<dyn Valuable>,
-    instructions: InstructionBlock,
-    else_block: Option<InstructionBlock>,
+    conditions: Expr,
+    instructions: Block,
+    else_branch: Option<Box<Stmt>>,
 }
 
 impl ConditionBlock {
     pub fn new(conditions: Box<dyn Valuable>, instructions:InstructionBlock) -> ConditionBlock {
-        ConditionBlock{conditions, instructions, else_block: None}
+        ConditionBlock{conditions: conditions.to_expr(), instructions: instructions.instructions, else_branch: None}
     }
 
     pub fn new_with_else(conditions: Box<dyn Valuable>, instructions:InstructionBlock, else_block: InstructionBlock) -> ConditionBlock {
-        ConditionBlock{conditions, instructions, else_block: Some(else_block)}
+        ConditionBlock{conditions: conditions.to_expr(), instructions: instructions.instructions, else_branch: Some(Box::new(Stmt::Block(else_block.instructions)))}
+    }
+
+    pub fn new_with_else_if(conditions: Box<dyn Valuable>, instructions:InstructionBlock, else_if: ConditionBlock) -> ConditionBlock {
+        ConditionBlock{conditions: conditions.to_expr(), instructions: instructions.instructions, else_branch: Some(Box::new(else_if.to_stmt().into_owned()))}
     }
 
 }
 
+impl Instruction for ConditionBlock {
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::If{condition: self.conditions.clone(), then_block: self.instructions.clone(), else_branch: self.else_branch.clone()})
+    }
+}
+
 pub struct InstructionBlock {
-    instructions: Vec<Box<dyn Instruction>>,
+    instructions: Block,
 }
 
 impl InstructionBlock {
     pub fn new(instructions: Vec<Box<dyn Instruction>>) -> InstructionBlock {
-        InstructionBlock{instructions}
+        InstructionBlock{instructions: instructions.iter().map(|instruction| instruction.to_stmt().into_owned()).collect()}
     }
 }
 
 impl Instruction for InstructionBlock {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value, CustomError> {
-        variables.enter_scope();
-        for instruction in &self.instructions {
-            match instruction.execute(variables) {
-                Ok(_) => (),
-                Err(e) => {
-                    variables.exit_scope();
-                    return Err(e)
-                },
-            }
-        }
-        variables.exit_scope();
-        return Ok(Value::Null())
-    }
-}
-
-impl Instruction for ConditionBlock {
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value, CustomError> {
-        let condition;
-        match self.conditions.get_value(variables) {
-            Ok(value) => {
-                match value {
-                    Value ::Boolean(b) => condition = b,
-                    Value::Null() => condition = false,
-                    _ => condition = false,
-                }
-            },
-            Err(e) => return Err(e),
-        }
-        if condition {
-            return self.instructions.execute(variables);
-        }
-
-        match &self.else_block {
-            Some(block) =>return block.execute(variables),
-            None => (),
-        }
-        return Ok(Value::Null())
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::Block(self.instructions.clone()))
     }
 }
 
 pub struct ConditionLoop{
-    condition: Box<dyn Valuable>,
-    instructions: InstructionBlock,
+    condition: Expr,
+    instructions: Block,
 }
 
 impl ConditionLoop{
     pub fn new(condition: Box<dyn Valuable>, instructions: InstructionBlock) -> ConditionLoop {
-        ConditionLoop{condition, instructions}
+        ConditionLoop{condition: condition.to_expr(), instructions: instructions.instructions}
     }
 }
 
 impl Instruction for ConditionLoop{
-    fn execute(&self, variables: &mut VariableManager) -> Result<Value, CustomError> {
-        let mut run = true;
-        let mut condition:bool;
-        while run{
-            match self.condition.get_value(variables) {
-                Ok(value) => {
-                    match value {
-                        Value ::Boolean(b) => condition = b,
-                        Value::Null() => condition = false,
-                        _ => condition = false,
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::While{condition: self.condition.clone(), body: self.instructions.clone()})
+    }
+}
+
+/// `for variable in iterable { instructions }`, each iteration running in a new scope holding the loop variables.
+pub struct ForLoop{
+    variables: Vec<String>,
+    iterable: Expr,
+    instructions: Block,
+}
+
+impl ForLoop{
+    pub fn new(variable: &str, iterable: Box<dyn Valuable>, instructions: InstructionBlock) -> ForLoop {
+        ForLoop{variables: vec![variable.to_string()], iterable: iterable.to_expr(), instructions: instructions.instructions}
+    }
+
+    /// Loop over the keys and values of a map.
+    pub fn new_with_value(key: &str, value: &str, iterable: Box<dyn Valuable>, instructions: InstructionBlock) -> ForLoop {
+        ForLoop{variables: vec![key.to_string(), value.to_string()], iterable: iterable.to_expr(), instructions: instructions.instructions}
+    }
+}
+
+impl Instruction for ForLoop{
+    fn to_stmt(&self) -> Cow<'_, Stmt> {
+        Cow::Owned(Stmt::For{variables: self.variables.clone(), iterable: self.iterable.clone(), body: self.instructions.clone(), span: None})
+    }
+}
+
+/// Evaluates `expr` in the current scope of `variables`.
+fn evaluate(expr: &Expr, variables: &mut VariableManager) -> Result<Value, CustomError> {
+    stack::guard(|| evaluate_in_place(expr, variables))
+}
+
+fn evaluate_in_place(expr: &Expr, variables: &mut VariableManager) -> Result<Value, CustomError> {
+    match expr {
+        Expr::Integer(value) => Ok(Value::new_integer(*value)),
+        Expr::Float(value) => Ok(Value::new_float(*value)),
+        Expr::String(value) => Ok(Value::new_string(value)),
+        Expr::Boolean(value) => Ok(Value::new_boolean(*value)),
+        Expr::Null => Ok(Value::new_null()),
+        Expr::Variable{name, span, slot} => variables.get_local_variable(name, slot).map_err(|e| locate(e, span)),
+        Expr::Binary{left, operator, right, span} => {
+            let left = evaluate(left, variables)?;
+            let right = evaluate(right, variables)?;
+            left.binary(*operator, &right).map_err(|e| locate(e, span))
+        },
+        Expr::Unary{operator, operand, span} => {
+            let value = evaluate(operand, variables)?;
+            value.unary(*operator).map_err(|e| locate(e, span))
+        },
+        Expr::Range{start, end, span} => {
+            let start = evaluate(start, variables)?;
+            let end = evaluate(end, variables)?;
+            Value::new_range(&start, &end).map_err(|e| locate(e, span))
+        },
+        Expr::Comparison{left, operator, right, span} => {
+            let left = evaluate(left, variables)?;
+            let right = evaluate(right, variables)?;
+            left.compare(*operator, &right).map(Value::new_boolean).map_err(|e| locate(e, span))
+        },
+        Expr::Logical{left, operator, right, span} => {
+            let left = get_boolean(evaluate(left, variables)?, span)?;
+            match operator {
+                '&' => {
+                    if !left {
+                        return Ok(Value::new_boolean(false));
                     }
                 },
-                Err(e) => return Err(e),
+                '|' => {
+                    if left {
+                        return Ok(Value::new_boolean(true));
+                    }
+                },
+                _ => return Err(locate(CustomError::new_operator_not_found_error(*operator), span)),
+            }
+            Ok(Value::new_boolean(get_boolean(evaluate(right, variables)?, span)?))
+        },
+        Expr::Call{callee, arguments, span} => {
+            let function = match &evaluate(callee, variables)? {
+                Value::Function(function) => Rc::clone(function),
+                value => return Err(locate(CustomError::new_operation_error(&format!("Cannot call {:?}", value)), span)),
+            };
+            let mut values = Vec::with_capacity(arguments.len());
+            for argument in arguments {
+                values.push(evaluate(argument, variables)?);
             }
-            if condition {
-               match self.instructions.execute(variables){
-                     Ok(_) => (),
-                     Err(e) => return Err(e),
-               }
-            }else{
-                run = false;
+            function.call(values, variables).map_err(|e| locate(e, span))
+        },
+        Expr::List{elements, ..} => {
+            let mut values = Vec::with_capacity(elements.len());
+            for element in elements {
+                values.push(evaluate(element, variables)?);
             }
+            Ok(Value::new_list(values))
+        },
+        Expr::Map{entries, span} => {
+            let mut values = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let key = evaluate(key, variables)?;
+                values.push((key, evaluate(value, variables)?));
+            }
+            Value::new_map(values).map_err(|e| locate(e, span))
+        },
+        Expr::Index{target, index, span} => {
+            let target = evaluate(target, variables)?;
+            let index = evaluate(index, variables)?;
+            target.get_index(&index).map_err(|e| locate(e, span))
+        },
+        Expr::Slice{target, start, end, span} => {
+            let target = evaluate(target, variables)?;
+            let start = evaluate_bound(start, variables)?;
+            let end = evaluate_bound(end, variables)?;
+            target.slice(&start, &end).map_err(|e| locate(e, span))
+        },
+    }
+}
+
+/// Value of a slice bound, `null` when it is left out.
+fn evaluate_bound(bound: &Option<Box<Expr>>, variables: &mut VariableManager) -> Result<Value, CustomError> {
+    match bound {
+        Some(bound) => evaluate(bound, variables),
+        None => Ok(Value::new_null()),
+    }
+}
+
+/// Runs `block` in a new scope, stopping at the first statement that does not complete normally.
+fn execute_block(block: &Block, variables: &mut VariableManager) -> Result<Flow, CustomError> {
+    stack::guard(|| execute_block_in_place(block, variables))
+}
+
+fn execute_block_in_place(block: &Block, variables: &mut VariableManager) -> Result<Flow, CustomError> {
+    variables.enter_scope();
+    for stmt in block {
+        match execute(stmt, variables) {
+            Ok(Flow::Normal(_)) => (),
+            Ok(flow) => {
+                variables.exit_scope();
+                return Ok(flow)
+            },
+            Err(e) => {
+                variables.exit_scope();
+                return Err(e)
+            },
         }
+    }
+    variables.exit_scope();
+    return Ok(Flow::Normal(Value::Null()))
+}
+
+fn execute(stmt: &Stmt, variables: &mut VariableManager) -> Result<Flow, CustomError> {
+    match stmt {
+        Stmt::Expr(expr) => Ok(Flow::Normal(evaluate(expr, variables)?)),
+        Stmt::Assign{name, value, slot, ..} => {
+            let value = evaluate(value, variables)?;
+            variables.set_variable(name, slot, value);
+            Ok(Flow::Normal(Value::Null()))
+        },
+        Stmt::SetIndex{target, index, value, span} => {
+            let target = evaluate(target, variables)?;
+            let index = evaluate(index, variables)?;
+            let value = evaluate(value, variables)?;
+            target.set_index(&index, value).map_err(|e| locate(e, span))?;
+            Ok(Flow::Normal(Value::new_null()))
+        },
+        Stmt::Block(block) => execute_block(block, variables),
+        Stmt::If{condition, then_block, else_branch} => {
+            if is_true(evaluate(condition, variables)?) {
+                return execute_block(then_block, variables);
+            }
+            match else_branch {
+                Some(stmt) => execute(stmt, variables),
+                None => Ok(Flow::Normal(Value::Null())),
+            }
+        },
+        Stmt::While{condition, body} => {
+            while is_true(evaluate(condition, variables)?) {
+                match execute_block(body, variables)? {
+                    Flow::Normal(_) | Flow::Continue => (),
+                    Flow::Break => break,
+                    flow => return Ok(flow),
+                }
+            }
+            Ok(Flow::Normal(Value::new_null()))
+        },
+        Stmt::For{variables: names, iterable, body, span} => {
+            let iterator = evaluate(iterable, variables)?.iterate(names.len()).map_err(|e| locate(e, span))?;
+            for (element, value) in iterator {
+                variables.enter_scope();
+                variables.declare_local(0, element);
+                if let (Some(value), 2) = (value, names.len()) {
+                    variables.declare_local(1, value);
+                }
+                let flow = execute_block(body, variables);
+                variables.exit_scope();
+                match flow? {
+                    Flow::Normal(_) | Flow::Continue => (),
+                    Flow::Break => break,
+                    flow => return Ok(flow),
+                }
+            }
+            Ok(Flow::Normal(Value::new_null()))
+        },
+        Stmt::Function(definition, slot) => {
+            variables.set_variable(&definition.name, slot, Value::Function(Rc::new(Function::new_defined(Rc::clone(definition)))));
+            Ok(Flow::Normal(Value::new_null()))
+        },
+        Stmt::Return{value, ..} => Ok(Flow::Return(evaluate(value, variables)?)),
+        Stmt::Break => Ok(Flow::Break),
+        Stmt::Continue => Ok(Flow::Continue),
+        Stmt::Throw{value, span} => Err(locate(CustomError::new_thrown_error(evaluate(value, variables)?), span)),
+        Stmt::Try{body, catch, finally} => {
+            let outcome = match (execute_block(body, variables), catch) {
+                (Err(error), Some((_, handler))) => {
+                    variables.enter_scope();
+                    variables.declare_local(0, error.into_value());
+                    let flow = execute_block(handler, variables);
+                    variables.exit_scope();
+                    flow
+                },
+                (outcome, _) => outcome,
+            };
+            // The `finally` block runs even after an error, `return`, `break` or `continue`, which it can override.
+            match finally {
+                Some(finally) => match execute_block(finally, variables)? {
+                    Flow::Normal(_) => outcome,
+                    flow => Ok(flow),
+                },
+                None => outcome,
+            }
+        },
+    }
+}
 
-        return Ok(Value::new_null())
+impl Default for Interpreter{
+    fn default() -> Interpreter{
+        Interpreter::new()
     }
 }
 
 impl Interpreter{
     pub fn new() -> Interpreter{
-        Interpreter{
+        let output: Output = Rc::new(RefCell::new(Box::new(io::stdout())));
+        let mut interpreter = Interpreter{
             variables:VariableManager::new(),
-        }
+            output: Rc::clone(&output),
+            engine: Engine::TreeWalker,
+        };
+        register_builtins(&mut interpreter, output);
+        interpreter
+    }
+
+    pub fn with_engine(engine: Engine) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_engine(engine);
+        interpreter
+    }
+
+    /// Switches the engine running the next instructions, variables and functions being kept.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    pub fn get_engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Redirects everything printed by the scripts, e.g. to capture it in tests.
+    pub fn set_output<W: Write + 'static>(&mut self, writer: W){
+        *self.output.borrow_mut() = Box::new(writer);
+    }
+
+    /// Exposes a Rust function to scripts as a global function taking exactly `arity` arguments.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, function: F)
+    where F: Fn(&[Value]) -> Result<Value, CustomError> + 'static {
+        let function = Function::new_native(name, Some(arity), Box::new(function));
+        self.variables.set_builtin(name, Value::Function(Rc::new(function)));
+    }
+
+    /// Exposes a Rust function to scripts as a global function taking any number of arguments.
+    pub fn register_variadic_function<F>(&mut self, name: &str, function: F)
+    where F: Fn(&[Value]) -> Result<Value, CustomError> + 'static {
+        let function = Function::new_native(name, None, Box::new(function));
+        self.variables.set_builtin(name, Value::Function(Rc::new(function)));
     }
 
     pub fn _get_variable(&mut self, name: &str) -> Result<Value, CustomError> {
         self.variables.get_variable(name)
     }
 
+    /// Runs a top-level instruction, resolved first so that its global variables are given the slots of this interpreter.
     pub fn execute (&mut self, instruction: &dyn Instruction) -> Result<Value,CustomError>{
-        let result = instruction.execute(&mut self.variables);
-        return result;
+        let mut stmt = instruction.to_stmt().into_owned();
+        Resolver::resolve(&mut stmt, &mut self.variables);
+        match self.engine {
+            Engine::TreeWalker => match execute(&stmt, &mut self.variables)? {
+                Flow::Normal(value) | Flow::Return(value) => Ok(value),
+                Flow::Break | Flow::Continue => Ok(Value::new_null()),
+            },
+            Engine::Bytecode => vm::run(&Compiler::compile_instruction(&stmt), &mut self.variables),
+        }
     }
 
-    pub fn execute_instructions(&mut self, instructions: &Vec<Box<dyn Instruction>>) -> Result<Value,CustomError>{
+    /// Runs a whole program, such as the statements returned by `Parser::parse_instructions`.
+    pub fn execute_instructions<I: Instruction>(&mut self, instructions: &[I]) -> Result<Value,CustomError>{
         for instruction in instructions {
-            match self.execute(instruction.deref()) {
+            match self.execute(instruction) {
                 Ok(_) => (),
                 Err(e) => return Err(e),
             }
@@ -361,4 +965,4 @@ impl Interpreter{
         return Ok(Value::new_null())
     }
 
-}
\ No newline at end of file
+}
@@ -1,86 +1,285 @@
 use std::vec::Vec;
 use crate::errors::CustomError;
+use crate::span::Span;
 use std::fmt::Debug;
 
+/// Arithmetic operators, `!` included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op{
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Not,
+}
+
+impl Op{
+    pub fn from_symbol(symbol: &str) -> Option<Op>{
+        match symbol {
+            "+" => Some(Op::Add),
+            "-" => Some(Op::Sub),
+            "*" => Some(Op::Mul),
+            "/" => Some(Op::Div),
+            "%" => Some(Op::Mod),
+            "!" => Some(Op::Not),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cmp{
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Cmp{
+    pub fn from_symbol(symbol: &str) -> Option<Cmp>{
+        match symbol {
+            ">" => Some(Cmp::Gt),
+            "<" => Some(Cmp::Lt),
+            ">=" => Some(Cmp::Ge),
+            "<=" => Some(Cmp::Le),
+            "==" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            _ => None,
+        }
+    }
+}
+
+/// Logical operators, spelled either as words (`and`) or symbols (`&&`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Logic{
+    And,
+    Or,
+    Not,
+}
+
+impl Logic{
+    pub fn from_symbol(symbol: &str) -> Option<Logic>{
+        match symbol {
+            "and" | "&&" => Some(Logic::And),
+            "or" | "||" => Some(Logic::Or),
+            "not" => Some(Logic::Not),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kw{
+    While,
+    If,
+    Else,
+    True,
+    False,
+    Null,
+    Fn,
+    Return,
+    For,
+    In,
+    Break,
+    Continue,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+}
+
+impl Kw{
+    pub fn from_word(word: &str) -> Option<Kw>{
+        match word {
+            "while" => Some(Kw::While),
+            "if" => Some(Kw::If),
+            "else" => Some(Kw::Else),
+            "true" => Some(Kw::True),
+            "false" => Some(Kw::False),
+            "null" => Some(Kw::Null),
+            "fn" => Some(Kw::Fn),
+            "return" => Some(Kw::Return),
+            "for" => Some(Kw::For),
+            "in" => Some(Kw::In),
+            "break" => Some(Kw::Break),
+            "continue" => Some(Kw::Continue),
+            "try" => Some(Kw::Try),
+            "catch" => Some(Kw::Catch),
+            "finally" => Some(Kw::Finally),
+            "throw" => Some(Kw::Throw),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind{
+    Identifier,
+    Integer,
+    Float,
+    String,
+    Operator(Op),
+    Comparator(Cmp),
+    Logical(Logic),
+    Keyword(Kw),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Semicolon,
+    Colon,
+    /// `..` between the bounds of a range.
+    DotDot,
+    Comma,
+    Assign,
+    /// Ends every token stream produced by the lexer.
+    Eof,
+}
+
 pub struct Token{
-    token_type: String,
+    kind: TokenKind,
     value: String,
+    /// Where the lexer found the token, `None` for the tokens built by hand.
+    span: Option<Span>,
 }
 
 impl Token{
 
-    pub fn new_identifier(value: &str) -> Token{
+    pub fn new(kind: TokenKind, value: &str) -> Token{
         Token{
-            token_type: "IDENTIFIER".to_string(),
+            kind,
             value: value.to_string(),
+            span: None,
         }
     }
 
+    pub fn new_identifier(value: &str) -> Token{
+        Token::new(TokenKind::Identifier, value)
+    }
+
     pub fn new_assign(value: &str) -> Token{
-        Token{
-            token_type: "ASSIGN".to_string(),
-            value: value.to_string(),
-        }
+        Token::new(TokenKind::Assign, value)
+    }
+
+    pub fn new_integer(value: &str) -> Token{
+        Token::new(TokenKind::Integer, value)
+    }
+
+    pub fn new_float(value: &str) -> Token{
+        Token::new(TokenKind::Float, value)
     }
 
+    /// Integer or float token, depending on whether `value` has a decimal point.
     pub fn new_number(value: &str) -> Token{
-        Token{
-            token_type: "NUMBER".to_string(),
-            value: value.to_string(),
+        if value.contains('.'){
+            return Token::new_float(value);
         }
+        Token::new_integer(value)
     }
 
     pub fn new_string(value: &str) -> Token{
-        Token{
-            token_type: "STRING".to_string(),
-            value: value.to_string(),
-        }
+        Token::new(TokenKind::String, value)
     }
 
+    /// `value` must be one of `+ - * / % !`.
     pub fn new_operator(value: &str) -> Token{
-        Token{
-            token_type: "OPERATOR".to_string(),
-            value: value.to_string(),
+        match Op::from_symbol(value) {
+            Some(op) => Token::new(TokenKind::Operator(op), value),
+            None => panic!("Unknown operator: {}", value),
         }
     }
 
+    /// `value` must be one of `> < >= <= == !=`.
     pub fn new_comparator(value: &str) -> Token{
-        Token{
-            token_type: "COMPARATOR".to_string(),
-            value: value.to_string(),
+        match Cmp::from_symbol(value) {
+            Some(cmp) => Token::new(TokenKind::Comparator(cmp), value),
+            None => panic!("Unknown comparator: {}", value),
         }
     }
 
     pub fn new_semicolon() -> Token{
-        Token{
-            token_type: "SEMICOLON".to_string(),
-            value: ";".to_string(),
-        }
+        Token::new(TokenKind::Semicolon, ";")
+    }
+
+    pub fn new_comma() -> Token{
+        Token::new(TokenKind::Comma, ",")
+    }
+
+    pub fn new_colon() -> Token{
+        Token::new(TokenKind::Colon, ":")
+    }
+
+    pub fn new_dot_dot() -> Token{
+        Token::new(TokenKind::DotDot, "..")
     }
 
+    /// `value` must be `(` or `)`.
     pub fn new_parenthesis(value: &str) -> Token{
-        Token{
-            token_type: "PARENTHESIS".to_string(),
-            value: value.to_string(),
+        match value {
+            "(" => Token::new(TokenKind::LParen, value),
+            ")" => Token::new(TokenKind::RParen, value),
+            _ => panic!("Unknown parenthesis: {}", value),
         }
     }
 
+    /// `value` must be `{` or `}`.
     pub fn new_bracket(value: &str) -> Token{
-        Token{
-            token_type: "BRACKET".to_string(),
-            value: value.to_string(),
+        match value {
+            "{" => Token::new(TokenKind::LBrace, value),
+            "}" => Token::new(TokenKind::RBrace, value),
+            _ => panic!("Unknown bracket: {}", value),
+        }
+    }
+
+    /// `value` must be `[` or `]`.
+    pub fn new_square_bracket(value: &str) -> Token{
+        match value {
+            "[" => Token::new(TokenKind::LBracket, value),
+            "]" => Token::new(TokenKind::RBracket, value),
+            _ => panic!("Unknown square bracket: {}", value),
         }
     }
 
+    /// `value` must be one of `and or not && ||`.
+    pub fn new_logical(value: &str) -> Token{
+        match Logic::from_symbol(value) {
+            Some(logic) => Token::new(TokenKind::Logical(logic), value),
+            None => panic!("Unknown logical operator: {}", value),
+        }
+    }
+
+    /// `value` must be a keyword such as `while` or `fn`.
     pub fn new_keyword(value: &str) -> Token{
-        Token{
-            token_type: "KEYWORD".to_string(),
-            value: value.to_string(),
+        match Kw::from_word(value) {
+            Some(keyword) => Token::new(TokenKind::Keyword(keyword), value),
+            None => panic!("Unknown keyword: {}", value),
         }
     }
 
-    pub fn get_token_type(&self) -> &str{
-        &self.token_type
+    pub fn new_eof() -> Token{
+        Token::new(TokenKind::Eof, "")
+    }
+
+    pub fn with_span(mut self, span: Span) -> Token{
+        self.span = Some(span);
+        self
+    }
+
+    pub fn get_span(&self) -> Option<Span>{
+        self.span
+    }
+
+    pub fn get_kind(&self) -> TokenKind{
+        self.kind
+    }
+
+    pub fn is(&self, kind: TokenKind) -> bool{
+        self.kind == kind
     }
 
     pub fn get_value(&self) -> &str{
@@ -88,7 +287,7 @@ impl Token{
     }
 
     pub fn equals(&self, token: &Token) -> bool{
-        self.token_type == token.token_type && self.value == token.value
+        self.kind == token.kind && self.value == token.value
     }
 
 }
@@ -96,8 +295,9 @@ impl Token{
 impl Clone for Token{
     fn clone(&self) -> Token{
         Token{
-            token_type: self.token_type.clone(),
+            kind: self.kind,
             value: self.value.clone(),
+            span: self.span,
         }
     }
 }
@@ -109,24 +309,57 @@ impl Debug for Token{
 }
 
 pub struct Lexer{
-    operators: Vec<char>,
-    comparator: Vec<char>,
-    keywords: Vec<String>,
+
+}
+
+impl Default for Lexer{
+    fn default() -> Lexer{
+        Lexer::new()
+    }
 }
 
 impl Lexer{
     pub fn new() -> Lexer{
-        Lexer{
-            operators: vec!['+', '-', '*', '/'],
-            comparator: vec!['>', '<'],
-            keywords: vec!["while".to_string(), "if".to_string(), "else".to_string()]
+        Lexer{}
+    }
+
+    /// Byte offset, line and column of every character of `input`, plus the position right after the last one.
+    fn get_positions(input: &str) -> Vec<(usize, usize, usize)>{
+        let mut positions = Vec::new();
+        let mut line = 1;
+        let mut column = 1;
+        for (offset, c) in input.char_indices(){
+            positions.push((offset, line, column));
+            if c == '\n'{
+                line += 1;
+                column = 1;
+            }else{
+                column += 1;
+            }
         }
+        positions.push((input.len(), line, column));
+        positions
     }
 
-    pub fn lex(&self, input: &String) -> Result<Vec<Token>,CustomError>{
+    pub fn lex(&self, input: &str) -> Result<Vec<Token>,CustomError>{
+        let (tokens, mut errors) = self.lex_with_diagnostics(input);
+        if !errors.is_empty(){
+            return Err(errors.remove(0));
+        }
+        Ok(tokens)
+    }
+
+    /// Lexes the whole input, skipping over invalid characters, and returns every error met along the way.
+    pub fn lex_with_diagnostics(&self, input: &str) -> (Vec<Token>, Vec<CustomError>){
         let mut tokens = Vec::new();
+        let mut errors = Vec::new();
         let mut i = 0;
         let chars: Vec<char> = input.chars().collect();
+        let positions = Self::get_positions(input);
+        let span = |start: usize, end: usize| {
+            let (offset, line, column) = positions[start];
+            Span::new(offset, positions[end].0, line, column)
+        };
         while i < chars.len(){
             let c = chars[i];
             if c == '#'{
@@ -135,7 +368,8 @@ impl Lexer{
                     j += 1;
                 }
                 if j >= chars.len() {
-                    return Err(CustomError::new_lexer_error("Comment not closed"));
+                    errors.push(CustomError::new_lexer_error("Comment not closed").with_span(span(i, i + 1)));
+                    break;
                 }
                 i = j+1;
                 continue;
@@ -145,17 +379,32 @@ impl Lexer{
                 continue;
             }
             if c == '(' || c == ')'{
-                tokens.push(Token::new_parenthesis(&c.to_string()));
+                tokens.push(Token::new_parenthesis(&c.to_string()).with_span(span(i, i + 1)));
                 i += 1;
                 continue;
             }
             if c == '{' || c == '}'{
-                tokens.push(Token::new_bracket(&c.to_string()));
+                tokens.push(Token::new_bracket(&c.to_string()).with_span(span(i, i + 1)));
+                i += 1;
+                continue;
+            }
+            if c == '[' || c == ']'{
+                tokens.push(Token::new_square_bracket(&c.to_string()).with_span(span(i, i + 1)));
+                i += 1;
+                continue;
+            }
+            if c == ':'{
+                tokens.push(Token::new_colon().with_span(span(i, i + 1)));
                 i += 1;
                 continue;
             }
             if c == ';'{
-                tokens.push(Token::new_semicolon());
+                tokens.push(Token::new_semicolon().with_span(span(i, i + 1)));
+                i += 1;
+                continue;
+            }
+            if c == ','{
+                tokens.push(Token::new_comma().with_span(span(i, i + 1)));
                 i += 1;
                 continue;
             }
@@ -164,23 +413,38 @@ impl Lexer{
                 while j < chars.len() && (chars[j].is_ascii_alphabetic() || chars[j] == '_' || chars[j].is_numeric()){
                     j += 1;
                 }
-                if self.keywords.contains(&chars[i..j].iter().collect::<String>()){
-                    tokens.push(Token::new_keyword(&chars[i..j].iter().collect::<String>()));
-                }else{
-                    tokens.push(Token::new_identifier(&chars[i..j].iter().collect::<String>()));
-                }
+                let word = chars[i..j].iter().collect::<String>();
+                let kind = match (Kw::from_word(&word), Logic::from_symbol(&word)) {
+                    (Some(keyword), _) => TokenKind::Keyword(keyword),
+                    (None, Some(logic)) => TokenKind::Logical(logic),
+                    (None, None) => TokenKind::Identifier,
+                };
+                tokens.push(Token::new(kind, &word).with_span(span(i, j)));
                 i = j;
                 continue;
             }
+            if c == '.' && i + 1 < chars.len() && chars[i+1] == '.'{
+                tokens.push(Token::new_dot_dot().with_span(span(i, i + 2)));
+                i += 2;
+                continue;
+            }
             if c.is_numeric() {
+                // `_` separates groups of digits, and `..` after a number starts a range instead of a decimal part.
                 let mut j = i;
-                while j < chars.len() && (chars[j].is_numeric() || chars[j] == '.'){
+                while j < chars.len() && (chars[j].is_numeric() || chars[j] == '_' || (chars[j] == '.' && chars.get(j + 1) != Some(&'.'))){
                     j += 1;
                 }
-                if chars[i..j].iter().collect::<String>().matches('.').count() > 1{
-                    return Err(CustomError::new_lexer_error("Invalid number"));
-                }
-                tokens.push(Token::new_number(&chars[i..j].iter().collect::<String>()));
+                let number = chars[i..j].iter().filter(|c| **c != '_').collect::<String>();
+                let kind = match number.matches('.').count() {
+                    0 => TokenKind::Integer,
+                    1 => TokenKind::Float,
+                    _ => {
+                        errors.push(CustomError::new_lexer_error("Invalid number").with_span(span(i, j)));
+                        i = j;
+                        continue;
+                    }
+                };
+                tokens.push(Token::new(kind, &number).with_span(span(i, j)));
                 i = j;
                 continue;
             }
@@ -189,35 +453,49 @@ impl Lexer{
                 while j < chars.len() && chars[j] != '"'{
                     j += 1;
                 }
-                if j == chars.len() && chars[j-1] != '"'{
-                    return Err(CustomError::new_lexer_error("String not closed"));
+                if j == chars.len(){
+                    errors.push(CustomError::new_lexer_error("String not closed").with_span(span(i, i + 1)));
+                    break;
                 }
-                tokens.push(Token::new_string(&chars[i+1..j].iter().collect::<String>()));
+                tokens.push(Token::new_string(&chars[i+1..j].iter().collect::<String>()).with_span(span(i, j + 1)));
                 i = j + 1;
                 continue;
             }
-            if self.operators.contains(&c){
-                tokens.push(Token::new_operator(&c.to_string()));
+            if (c == '=' || c == '!') && i + 1 < chars.len() && chars[i+1] == '='{
+                tokens.push(Token::new_comparator(&format!("{}=", c)).with_span(span(i, i + 2)));
+                i += 2;
+                continue;
+            }
+            if (c == '&' || c == '|') && i + 1 < chars.len() && chars[i+1] == c{
+                tokens.push(Token::new_logical(&format!("{}{}", c, c)).with_span(span(i, i + 2)));
+                i += 2;
+                continue;
+            }
+            if let Some(op) = Op::from_symbol(&c.to_string()){
+                tokens.push(Token::new(TokenKind::Operator(op), &c.to_string()).with_span(span(i, i + 1)));
                 i += 1;
                 continue;
             }
-            if self.comparator.contains(&c){
-                let mut j = i;
-                while j < chars.len() && (self.comparator.contains(&chars[j]) || chars[j] == '='){
-                    j += 1;
+            if c == '>' || c == '<'{
+                if i + 1 < chars.len() && chars[i+1] == '='{
+                    tokens.push(Token::new_comparator(&format!("{}=", c)).with_span(span(i, i + 2)));
+                    i += 2;
+                }else{
+                    tokens.push(Token::new_comparator(&c.to_string()).with_span(span(i, i + 1)));
+                    i += 1;
                 }
-                tokens.push(Token::new_comparator(&chars[i..j].iter().collect::<String>()));
-                i = j;
                 continue;
             }
             if c == '='{
-                tokens.push(Token::new_assign("="));
+                tokens.push(Token::new_assign("=").with_span(span(i, i + 1)));
                 i += 1;
                 continue;
             }
-            return Err(CustomError::new_lexer_error(&format!("Unknown character: {}", c)));
+            errors.push(CustomError::new_lexer_error(&format!("Unknown character: {}", c)).with_span(span(i, i + 1)));
+            i += 1;
         }
-        Ok(tokens)
+        tokens.push(Token::new_eof().with_span(span(chars.len(), chars.len())));
+        (tokens, errors)
     }
 }
 
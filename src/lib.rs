@@ -1,7 +1,17 @@
+pub mod ast;
 pub mod interpreter;
+pub mod builtins;
 pub mod errors;
+pub mod diagnostics;
+pub mod span;
 pub mod value;
+pub mod map;
 pub mod parser;
+pub mod resolver;
 
 pub mod lexer;
-mod variables;
\ No newline at end of file
+mod variables;
+mod stack;
+mod compiler;
+mod vm;
+mod runtime;
\ No newline at end of file
@@ -1,19 +1,13 @@
-mod interpreter;
-mod errors;
-mod value;
-mod parser;
-mod lexer;
-
-mod variables;
-
 use std::io;
+use std::io::IsTerminal;
 use std::fs;
 use std::env;
-use crate::errors::CustomError;
+use krab_language::errors::CustomError;
+use krab_language::diagnostics::Renderer;
 
-use crate::lexer::Lexer;
-use crate::parser::Parser;
-use crate::interpreter::Interpreter;
+use krab_language::lexer::Lexer;
+use krab_language::parser::Parser;
+use krab_language::interpreter::{Engine, Interpreter};
 
 
 fn next_line() -> String {
@@ -22,8 +16,10 @@ fn next_line() -> String {
     return input
 }
 
-fn print_error(error: &CustomError){
-    eprintln!("\x1b[31m{}\x1b[0m", error);
+/// Prints `error` as a diagnostic quoting `source`, colored only when stderr is a terminal.
+fn print_error(error: &CustomError, source: &str, file: &str){
+    let renderer = Renderer::new(io::stderr().is_terminal());
+    eprint!("{}", renderer.render(error, source, file));
 }
 
 fn read_file(file_path: &str) -> Result<String, CustomError>{
@@ -39,11 +35,10 @@ fn terminal_loop(lexer: Lexer, parser: Parser, interpreter: &mut Interpreter){
         print!(">>>");
         io::Write::flush(&mut io::stdout()).expect("Flush failed!");
         let input = next_line();
-        let tokens;
-        match lexer.lex(&input) {
-            Ok(tks) => tokens= tks,
+        let tokens = match lexer.lex(&input) {
+            Ok(tks) => tks,
             Err(error) => {
-                print_error(&error);
+                print_error(&error, &input, "<stdin>");
                 continue;
             }
         };
@@ -51,49 +46,55 @@ fn terminal_loop(lexer: Lexer, parser: Parser, interpreter: &mut Interpreter){
             Ok(inst) => {
                 match interpreter.execute_instructions(&inst) {
                     Ok(_) => println!("{:?}", interpreter.variables),
-                    Err(error) => print_error(&error),
+                    Err(error) => print_error(&error, &input, "<stdin>"),
                 };
             }
             Err(error) => {
-                print_error(&error);
+                print_error(&error, &input, "<stdin>");
                 continue;
             }
         };
     }
 }
 
+/// Engine named by the `KRAB_ENGINE` environment variable (`tree` or `bytecode`), the tree walker by default.
+fn engine_from_env() -> Engine {
+    match env::var("KRAB_ENGINE") {
+        Ok(name) => Engine::from_name(&name).unwrap_or(Engine::TreeWalker),
+        Err(_) => Engine::TreeWalker,
+    }
+}
+
 fn main() {
     let lexer = Lexer::new();
     let parser = Parser::new();
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = Interpreter::with_engine(engine_from_env());
 
 
     let args: Vec<String> = env::args().collect();    
     if args.len() > 1 {
         let file_path = &args[1];
-        let content :String;
-        match read_file(file_path){
-            Ok(c) => content = c,
+        let content = match read_file(file_path){
+            Ok(c) => c,
             Err(error) => {
-                print_error(&error);
+                print_error(&error, "", file_path);
                 return;
             }
         };
-        let tokens; 
-        
-        match lexer.lex(&content){
-            Ok(tks) => tokens = tks,
-            Err(e) => {print_error(&e); return;}
-        }
-        let instructions;
-        match parser.parse_instructions(tokens){
-            Ok(inst) => instructions = inst,
-            Err(e) => {print_error(&e); return;}
+        // report every lexer and parser error at once rather than one per run
+        let (tokens, mut errors) = lexer.lex_with_diagnostics(&content);
+        let (instructions, parser_errors) = parser.parse_instructions_with_diagnostics(tokens);
+        errors.extend(parser_errors);
+        if !errors.is_empty() {
+            for error in &errors {
+                print_error(error, &content, file_path);
+            }
+            return;
         }
         match interpreter.execute_instructions(&instructions){
             Ok(_) => (),
             Err(error) => {
-                print_error(&error);
+                print_error(&error, &content, file_path);
             }
         }
         //print the variables
@@ -0,0 +1,105 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use crate::errors::CustomError;
+use crate::value::Value;
+
+/// Entries of a map in insertion order, indexed by the hash of their keys so that finding a key does not compare it
+/// to every other one.
+#[derive(Default)]
+pub struct Entries {
+    entries: Vec<(Value, Value)>,
+    /// Position of the last entry added for each hash.
+    first: HashMap<u64, usize>,
+    /// For each entry, the position of the previous entry whose key has the same hash.
+    next: Vec<Option<usize>>,
+    /// Positions of the entries whose key has no hash.
+    unhashed: Vec<usize>,
+}
+
+/// Hash of a map key, the same for keys equal by `Value::eq` such as `1` and `1.0`. Lists and maps have none as they
+/// may change after being used as keys, and neither has NaN which is equal to nothing.
+fn hash_key(key: &Value) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    match key {
+        Value::Integer(i) => (0u8, i).hash(&mut hasher),
+        Value::Float(f) if f.is_nan() => return None,
+        // A whole float hashes like the integer it is equal to, 2^63 being the first float above every integer.
+        Value::Float(f) if f.fract() == 0.0 && (-9223372036854775808.0..9223372036854775808.0).contains(f) => (0u8, *f as i64).hash(&mut hasher),
+        Value::Float(f) => (1u8, f.to_bits()).hash(&mut hasher),
+        Value::String(s) => (2u8, s).hash(&mut hasher),
+        Value::Boolean(b) => (3u8, b).hash(&mut hasher),
+        Value::Null() => 4u8.hash(&mut hasher),
+        Value::Function(function) => (5u8, Rc::as_ptr(function)).hash(&mut hasher),
+        Value::Range(start, end) => (6u8, start, end).hash(&mut hasher),
+        Value::List(_) | Value::Map(_) => return None,
+    }
+    Some(hasher.finish())
+}
+
+impl Entries {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (Value, Value)> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self, position: usize) -> Option<&(Value, Value)> {
+        self.entries.get(position)
+    }
+
+    /// Position of the entry holding `key`, comparing it only to the keys with the same hash.
+    pub fn find(&self, key: &Value) -> Result<Option<usize>, CustomError> {
+        let mut candidate = match hash_key(key) {
+            Some(hash) => self.first.get(&hash).copied(),
+            None => {
+                for &position in &self.unhashed {
+                    if self.entries[position].0.eq(key)? {
+                        return Ok(Some(position));
+                    }
+                }
+                return Ok(None);
+            },
+        };
+        while let Some(position) = candidate {
+            if self.entries[position].0.eq(key)? {
+                return Ok(Some(position));
+            }
+            candidate = self.next[position];
+        }
+        Ok(None)
+    }
+
+    /// Replaces the value of the entry at `position`.
+    pub fn set_value(&mut self, position: usize, value: Value) {
+        self.entries[position].1 = value;
+    }
+
+    /// Adds an entry after the others, `key` not being in the map yet.
+    pub fn push(&mut self, key: Value, value: Value) {
+        let position = self.entries.len();
+        match hash_key(&key) {
+            Some(hash) => self.next.push(self.first.insert(hash, position)),
+            None => {
+                self.unhashed.push(position);
+                self.next.push(None);
+            },
+        }
+        self.entries.push((key, value));
+    }
+
+    /// Removes every entry, in insertion order.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, (Value, Value)> {
+        self.first.clear();
+        self.next.clear();
+        self.unhashed.clear();
+        self.entries.drain(..)
+    }
+}
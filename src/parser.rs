@@ -1,72 +1,387 @@
-use crate::interpreter::{Instruction, Operation, FloatValue, StringValue, Variable, Affectation, Valuable, IntegerValue, Condition, InstructionBlock, ConditionLoop, ConditionBlock};
-use crate::lexer::Token;
+use crate::ast::{Block, Expr, FunctionDef, Slot, Stmt};
+use crate::lexer::{Token, TokenKind, Op, Cmp, Logic, Kw};
 use crate::errors::CustomError;
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::stack;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 pub struct Parser{
+    /// Errors of the statements skipped while recovering, see `parse_instructions_with_diagnostics`.
+    diagnostics: RefCell<Vec<CustomError>>,
+    /// Number of expressions and blocks enclosing the one being parsed.
+    depth: Cell<usize>,
+}
+
+/// Deepest nesting of expressions and blocks accepted, keeping the syntax tree shallow enough to be walked safely.
+pub const MAX_NESTING: usize = 256;
+
+/// Where the instructions being parsed are nested, used to reject misplaced statements such as `return` or `break`.
+#[derive(Clone, Copy)]
+struct Context{
+    in_function: bool,
+    in_loop: bool,
+    /// Record bad statements in `Parser::diagnostics` and carry on instead of failing.
+    recovering: bool,
+}
+
+impl Context{
+    const TOP_LEVEL: Context = Context{in_function: false, in_loop: false, recovering: false};
+
+    /// A function body, where the loops around the declaration can no longer be exited.
+    fn in_function(self) -> Context{
+        Context{in_function: true, in_loop: false, ..self}
+    }
+
+    fn in_loop(self) -> Context{
+        Context{in_loop: true, ..self}
+    }
+}
+
+/// Points `error` at `token`, unless the token was built by hand without a location.
+fn locate_at(error: CustomError, token: &Token) -> CustomError{
+    match token.get_span() {
+        Some(span) => error.with_span(span),
+        None => error,
+    }
+}
 
+/// A parser error pointing at `token`.
+fn error_at(message: &str, token: &Token) -> CustomError{
+    locate_at(CustomError::new_parser_error(message), token)
+}
+
+const BLOCK_SEPARATOR_NOTE: &str = "a block must be followed by ';' before the next statement";
+
+/// Adds a secondary label pointing at the `owner` keyword that opened a block.
+fn label_block(error: CustomError, owner: &Token) -> CustomError{
+    match owner.get_span() {
+        Some(span) => error.with_label(span, get_block_label(owner)),
+        None => error,
+    }
+}
+
+/// Text of the label pointing at the keyword that opened a block.
+fn get_block_label(owner: &Token) -> &'static str{
+    match owner.get_value() {
+        "while" | "for" => "loop started here",
+        "if" => "condition started here",
+        "else" => "else block started here",
+        "try" | "catch" | "finally" => "try statement started here",
+        "fn" => "function started here",
+        _ => "block started here",
+    }
+}
+
+/// Span running from the first to the last of `tokens`, which must not be empty, if they have a location.
+fn span_of(tokens: &[Token]) -> Option<Span>{
+    match (tokens[0].get_span(), tokens[tokens.len() - 1].get_span()) {
+        (Some(first), Some(last)) => Some(first.to(&last)),
+        (first, _) => first,
+    }
+}
+
+impl Default for Parser{
+    fn default() -> Parser{
+        Parser::new()
+    }
 }
 
 impl Parser{
     pub fn new() -> Parser{
-        Parser{}
+        Parser{diagnostics: RefCell::new(Vec::new()), depth: Cell::new(0)}
     }
 
-    fn is_value(token: &Token) -> bool{
-        let token_type = token.get_token_type();
-        token_type == Token::new_identifier("").get_token_type() || token_type == Token::new_number("0.0").get_token_type() || token_type == Token::new_string("").get_token_type()
+    /// Goes one level deeper in the program, refusing programs nested more than `MAX_NESTING` levels deep.
+    fn deepen(&self, token: Option<&Token>) -> Result<(), CustomError>{
+        if self.depth.get() >= MAX_NESTING{
+            let error = CustomError::new_parser_error(&format!("Too deeply nested, at most {} levels are allowed", MAX_NESTING));
+            return match token {
+                Some(token) => Err(locate_at(error, token)),
+                None => Err(error),
+            };
+        }
+        self.depth.set(self.depth.get() + 1);
+        return Ok(());
+    }
+
+    /// Runs `parse` one level deeper in the program.
+    fn nested<T>(&self, token: Option<&Token>, parse: impl FnOnce() -> Result<T, CustomError>) -> Result<T, CustomError>{
+        self.deepen(token)?;
+        let result = stack::guard(parse);
+        self.depth.set(self.depth.get() - 1);
+        return result;
     }
 
-    fn get_valuable(&self, tokens: Vec<Token>) -> Result<Box<dyn Valuable>, CustomError >{
-        if tokens.len() == 0{
+    /// Runs `parse`, which folds operators into an ever deeper expression with `deepen`, then returns to the current
+    /// depth.
+    fn folding<T>(&self, parse: impl FnOnce() -> Result<T, CustomError>) -> Result<T, CustomError>{
+        let depth = self.depth.get();
+        let result = parse();
+        self.depth.set(depth);
+        return result;
+    }
+
+    fn get_expression(&self, tokens: Vec<Token>) -> Result<Expr, CustomError>{
+        if tokens.is_empty(){
             return Err(CustomError::new_parser_error("Value expected but none found"));
-        }else if tokens.len() == 1{
-            let token_type = tokens[0].get_token_type();
-            if token_type == Token::new_identifier("").get_token_type(){
-                return Ok(Box::new(Variable::new(tokens[0].get_value())));
-            }else if token_type == Token::new_number("0.0").get_token_type(){
-                if tokens[0].get_value().contains("."){
-                    return Ok(Box::new(FloatValue::new(tokens[0].get_value().parse::<f64>().unwrap())));
-                }else{
-                    return Ok(Box::new(IntegerValue::new(tokens[0].get_value().parse::<i64>().unwrap())));
-                }
-            }else if token_type == Token::new_string("").get_token_type(){
-                return Ok(Box::new(StringValue::new(tokens[0].get_value())));
-            }
-            return Err(CustomError::new_parser_error(&format!("Unexpected value: {}", tokens[0].get_value())));
-        }else{
-            if tokens.len() == 3{
-                if Self::is_value(&tokens[0]) && Self::is_value(&tokens[2]){
-                    if tokens[1].get_token_type() == Token::new_operator("+").get_token_type(){
-                        let left = self.get_valuable(vec![tokens[0].clone()])?;
-                        let right = self.get_valuable(vec![tokens[2].clone()])?;
-                        let operator:char = tokens[1].get_value().chars().nth(0).unwrap();
-                        return Ok(Box::new(Operation::new(left, right, operator)));
-                    }else if tokens[1].get_token_type() == Token::new_comparator(">").get_token_type(){
-                        let left = self.get_valuable(vec![tokens[0].clone()])?;
-                        let right = self.get_valuable(vec![tokens[2].clone()])?;
-                        let comparator:char;
-                        match tokens[1].get_value() {
-                            ">" => comparator = '>',
-                            "<" => comparator = '<',
-                            _ => return Err(CustomError::new_parser_error("Not implemented yet")),
-                        }
-                        return Ok(Box::new(Condition::new(left, right, comparator)));
-                    }
+        }
+        let mut position = 0;
+        let expression = self.parse_expression(&tokens, &mut position, 0)?;
+        if position < tokens.len(){
+            return Err(error_at(&format!("Unexpected token: {}", tokens[position].get_value()), &tokens[position]));
+        }
+        return Ok(expression);
+    }
 
+    /// `not` binds looser than comparisons so that `not a == b` negates the comparison.
+    const COMPARISON_PRECEDENCE: u8 = 3;
+
+    /// Binding power of a binary operator token, `None` if the token does not continue an expression.
+    fn get_precedence(token: &Token) -> Option<u8>{
+        match token.get_kind() {
+            TokenKind::Logical(Logic::Or) => return Some(1),
+            TokenKind::Logical(Logic::And) => return Some(2),
+            TokenKind::Comparator(_) => return Some(Self::COMPARISON_PRECEDENCE),
+            TokenKind::DotDot => return Some(4),
+            TokenKind::Operator(Op::Add) | TokenKind::Operator(Op::Sub) => return Some(5),
+            TokenKind::Operator(Op::Mul) | TokenKind::Operator(Op::Div) | TokenKind::Operator(Op::Mod) => return Some(6),
+            _ => return None,
+        }
+    }
+
+    /// Precedence climbing: parses operands and binary operators whose precedence is at least `min_precedence`.
+    fn parse_expression(&self, tokens: &[Token], position: &mut usize, min_precedence: u8) -> Result<Expr, CustomError>{
+        self.folding(|| self.parse_expression_in(tokens, position, min_precedence))
+    }
+
+    /// Each operator nests the expression parsed so far one level deeper, so a long chain counts towards `MAX_NESTING`.
+    fn parse_expression_in(&self, tokens: &[Token], position: &mut usize, min_precedence: u8) -> Result<Expr, CustomError>{
+        let start = *position;
+        let mut left = self.parse_primary(tokens, position)?;
+        while *position < tokens.len(){
+            let precedence = match Self::get_precedence(&tokens[*position]) {
+                Some(p) if p >= min_precedence => p,
+                _ => break,
+            };
+            let operator = tokens[*position].clone();
+            self.deepen(Some(&operator))?;
+            *position += 1;
+            let right = self.parse_expression(tokens, position, precedence + 1)?;
+            left = self.build_binary(&operator, left, right, span_of(&tokens[start..*position]))?;
+        }
+        return Ok(left);
+    }
+
+    fn build_binary(&self, operator: &Token, left: Expr, right: Expr, span: Option<Span>) -> Result<Expr, CustomError>{
+        match operator.get_kind() {
+            TokenKind::Logical(Logic::And) => return Ok(Expr::Logical{left: Box::new(left), operator: '&', right: Box::new(right), span}),
+            TokenKind::Logical(Logic::Or) => return Ok(Expr::Logical{left: Box::new(left), operator: '|', right: Box::new(right), span}),
+            TokenKind::DotDot => return Ok(Expr::Range{start: Box::new(left), end: Box::new(right), span}),
+            TokenKind::Comparator(cmp) => {
+                let comparator = match cmp {
+                    Cmp::Gt => '>',
+                    Cmp::Lt => '<',
+                    Cmp::Ge => '≥',
+                    Cmp::Le => '≤',
+                    Cmp::Eq => '=',
+                    Cmp::Ne => '≠',
+                };
+                return Ok(Expr::Comparison{left: Box::new(left), operator: comparator, right: Box::new(right), span});
+            }
+            TokenKind::Operator(op) => {
+                let operation = match op {
+                    Op::Add => '+',
+                    Op::Sub => '-',
+                    Op::Mul => '*',
+                    Op::Div => '/',
+                    Op::Mod => '%',
+                    Op::Not => return Err(error_at("Unknown operator: !", operator)),
+                };
+                return Ok(Expr::Binary{left: Box::new(left), operator: operation, right: Box::new(right), span});
+            }
+            _ => return Err(error_at(&format!("Unexpected token: {}", operator.get_value()), operator)),
+        }
+    }
+
+    fn parse_primary(&self, tokens: &[Token], position: &mut usize) -> Result<Expr, CustomError>{
+        self.nested(tokens.get(*position), || self.parse_primary_in(tokens, position))
+    }
+
+    fn parse_primary_in(&self, tokens: &[Token], position: &mut usize) -> Result<Expr, CustomError>{
+        if *position >= tokens.len(){
+            return Err(CustomError::new_parser_error("Value expected but none found"));
+        }
+        let start = *position;
+        let token = &tokens[*position];
+        *position += 1;
+        match token.get_kind() {
+            TokenKind::Identifier => {
+                return self.parse_postfix(Expr::Variable{name: token.get_value().to_string(), span: token.get_span(), slot: Slot::Global}, tokens, start, position);
+            }
+            TokenKind::Float => match token.get_value().parse::<f64>() {
+                Ok(value) => return Ok(Expr::Float(value)),
+                Err(_) => return Err(error_at(&format!("Invalid number: {}", token.get_value()), token)),
+            },
+            TokenKind::Integer => match token.get_value().parse::<i64>() {
+                Ok(value) => return Ok(Expr::Integer(value)),
+                Err(_) => return Err(error_at(&format!("Invalid number: {}", token.get_value()), token)),
+            },
+            TokenKind::String => return Ok(Expr::String(token.get_value().to_string())),
+            TokenKind::Keyword(Kw::True) => return Ok(Expr::Boolean(true)),
+            TokenKind::Keyword(Kw::False) => return Ok(Expr::Boolean(false)),
+            TokenKind::Keyword(Kw::Null) => return Ok(Expr::Null),
+            TokenKind::Operator(Op::Sub) => {
+                if *position < tokens.len() && matches!(tokens[*position].get_kind(), TokenKind::Integer | TokenKind::Float){
+                    // fold the sign into the literal so that i64::MIN can be written
+                    let literal = &tokens[*position];
+                    let mut number = Token::new(literal.get_kind(), &format!("-{}", literal.get_value()));
+                    *position += 1;
+                    if let Some(span) = span_of(&tokens[start..*position]){
+                        number = number.with_span(span);
+                    }
+                    return self.parse_primary(&[number], &mut 0);
                 }
+                // unary operators bind tighter than any binary operator
+                let operand = self.parse_primary(tokens, position)?;
+                return Ok(Expr::Unary{operator: '-', operand: Box::new(operand), span: span_of(&tokens[start..*position])});
+            }
+            TokenKind::Operator(Op::Not) => {
+                let operand = self.parse_primary(tokens, position)?;
+                return Ok(Expr::Unary{operator: '!', operand: Box::new(operand), span: span_of(&tokens[start..*position])});
+            }
+            TokenKind::Logical(Logic::Not) => {
+                let operand = self.parse_expression(tokens, position, Self::COMPARISON_PRECEDENCE)?;
+                return Ok(Expr::Unary{operator: '!', operand: Box::new(operand), span: span_of(&tokens[start..*position])});
+            }
+            TokenKind::LParen => {
+                let expression = self.parse_expression(tokens, position, 0)?;
+                if *position >= tokens.len() || !tokens[*position].is(TokenKind::RParen){
+                    return Err(error_at("')' expected but none found", token));
+                }
+                *position += 1;
+                return self.parse_postfix(expression, tokens, start, position);
+            }
+            TokenKind::LBracket => {
+                let elements = self.parse_arguments(tokens, position, TokenKind::RBracket, "']' expected but none found", token)?;
+                let list = Expr::List{elements, span: span_of(&tokens[start..*position])};
+                return self.parse_postfix(list, tokens, start, position);
+            }
+            TokenKind::LBrace => {
+                let map = self.parse_map(tokens, start, position)?;
+                return self.parse_postfix(map, tokens, start, position);
+            }
+            _ => {}
+        }
+        return Err(error_at(&format!("Unexpected value: {}", token.get_value()), token));
+    }
+
+    /// Parses comma separated expressions up to the `closing` token, `*position` being right after the `opening` one.
+    fn parse_arguments(&self, tokens: &[Token], position: &mut usize, closing: TokenKind, message: &str, opening: &Token) -> Result<Vec<Expr>, CustomError>{
+        let mut arguments = Vec::new();
+        while *position < tokens.len() && !tokens[*position].is(closing){
+            arguments.push(self.parse_expression(tokens, position, 0)?);
+            if *position < tokens.len() && tokens[*position].is(TokenKind::Comma){
+                *position += 1;
+            }else{
+                break;
+            }
+        }
+        if *position >= tokens.len() || !tokens[*position].is(closing){
+            return Err(error_at(message, opening));
+        }
+        *position += 1;
+        return Ok(arguments);
+    }
+
+    /// Parses the `key: value` entries of a map literal, `*position` being right after the '{' at `tokens[start]`.
+    fn parse_map(&self, tokens: &[Token], start: usize, position: &mut usize) -> Result<Expr, CustomError>{
+        let mut entries = Vec::new();
+        while *position < tokens.len() && !tokens[*position].is(TokenKind::RBrace){
+            let key = self.parse_expression(tokens, position, 0)?;
+            if *position >= tokens.len() || !tokens[*position].is(TokenKind::Colon){
+                return Err(error_at("':' expected after a map key", &tokens[*position - 1]));
+            }
+            *position += 1;
+            entries.push((key, self.parse_expression(tokens, position, 0)?));
+            if *position < tokens.len() && tokens[*position].is(TokenKind::Comma){
+                *position += 1;
+            }else{
+                break;
+            }
+        }
+        if *position >= tokens.len() || !tokens[*position].is(TokenKind::RBrace){
+            return Err(error_at("'}' expected but none found", &tokens[start]));
+        }
+        *position += 1;
+        return Ok(Expr::Map{entries, span: span_of(&tokens[start..*position])});
+    }
+
+    /// Parses the argument lists and indexes following a value starting at `tokens[start]`, as in `f(a)(b)` or `xs[0][1:]`.
+    fn parse_postfix(&self, value: Expr, tokens: &[Token], start: usize, position: &mut usize) -> Result<Expr, CustomError>{
+        self.folding(|| self.parse_postfix_in(value, tokens, start, position))
+    }
 
+    /// Each call or index nests the value one level deeper, like the operators of `parse_expression_in`.
+    fn parse_postfix_in(&self, value: Expr, tokens: &[Token], start: usize, position: &mut usize) -> Result<Expr, CustomError>{
+        let mut value = value;
+        while *position < tokens.len(){
+            let opening = &tokens[*position];
+            if opening.is(TokenKind::LParen) || opening.is(TokenKind::LBracket){
+                self.deepen(Some(opening))?;
+            }
+            if opening.is(TokenKind::LParen){
+                *position += 1;
+                let arguments = self.parse_arguments(tokens, position, TokenKind::RParen, "')' expected but none found", opening)?;
+                value = Expr::Call{callee: Box::new(value), arguments, span: span_of(&tokens[start..*position])};
+            }else if opening.is(TokenKind::LBracket){
+                *position += 1;
+                value = self.parse_index(value, tokens, start, position)?;
+            }else{
+                break;
             }
-            return Err(CustomError::new_parser_error("Not implemented yet"));
+        }
+        return Ok(value);
+    }
+
+    /// Parses `[index]` or `[start:end]` applied to `target`, `*position` being right after the '['.
+    fn parse_index(&self, target: Expr, tokens: &[Token], start: usize, position: &mut usize) -> Result<Expr, CustomError>{
+        let opening = &tokens[*position - 1];
+        let is = |position: usize, kind: TokenKind| position < tokens.len() && tokens[position].is(kind);
+        if is(*position, TokenKind::RBracket){
+            return Err(error_at("Index expected but none found", opening));
+        }
+        let mut first = None;
+        if !is(*position, TokenKind::Colon){
+            first = Some(Box::new(self.parse_expression(tokens, position, 0)?));
+        }
+        let is_slice = is(*position, TokenKind::Colon);
+        let mut end = None;
+        if is_slice{
+            *position += 1;
+            if !is(*position, TokenKind::RBracket){
+                end = Some(Box::new(self.parse_expression(tokens, position, 0)?));
+            }
+        }
+        if !is(*position, TokenKind::RBracket){
+            return Err(error_at("']' expected but none found", opening));
+        }
+        *position += 1;
+        let span = span_of(&tokens[start..*position]);
+        match (first, is_slice) {
+            (Some(index), false) => return Ok(Expr::Index{target: Box::new(target), index, span}),
+            (first, _) => return Ok(Expr::Slice{target: Box::new(target), start: first, end, span}),
         }
     }
 
     fn get_end_of_block(&self, tokens: Vec<Token>) -> Result<usize, CustomError>{
         let mut j = 0;
         let mut bracket_count = 0;
-        while j < tokens.len() && (!tokens[j].equals(&Token::new_bracket("}")) || bracket_count != 0){
-            if tokens[j].equals(&Token::new_bracket("{")){
+        while j < tokens.len() && (!tokens[j].is(TokenKind::RBrace) || bracket_count != 0){
+            if tokens[j].is(TokenKind::LBrace){
                 bracket_count += 1;
-            }else if tokens[j].equals(&Token::new_bracket("}")){
+            }else if tokens[j].is(TokenKind::RBrace){
                 bracket_count -= 1;
             }
             j += 1;
@@ -82,25 +397,261 @@ impl Parser{
         return Ok(j);
     }
 
-    pub fn parse_instructions(&self, tokens: Vec<Token>) -> Result<Vec<Box<dyn Instruction>>, CustomError>{
+    /// Parses `condition { instructions }` following the `owner` keyword and returns the index of the closing '}' in `tokens`.
+    fn parse_condition_and_block(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<(Expr, Block, usize), CustomError>{
+        // The block starts at the first '{' outside parentheses and brackets, a map in a condition being parenthesized.
+        let mut i = 0;
+        let mut depth = 0;
+        while i < tokens.len() && (depth > 0 || !tokens[i].is(TokenKind::LBrace)){
+            match tokens[i].get_kind() {
+                TokenKind::LParen | TokenKind::LBracket => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        if i == tokens.len(){
+            let error = CustomError::new_parser_error("'{' expected but none found");
+            match tokens.last() {
+                Some(token) => return Err(locate_at(error, token)),
+                None => return Err(error),
+            }
+        }
+        if Self::starts_map_literal(&tokens[i..]){
+            return Err(error_at("Map literal found where a block was expected", &tokens[i]).with_note("wrap the map literal in parentheses"));
+        }
+        let condition = self.get_expression(tokens[0..i].to_vec()).map_err(|e| locate_at(e, &tokens[i]))?;
+        let (instructions, end) = self.parse_block(owner, &tokens[i..], context)?;
+        return Ok((condition, instructions, end + i));
+    }
+
+    /// Whether the '{' at `tokens[0]` opens a map literal, its first `key: value` entry having a ':' that no statement
+    /// of a block can have outside brackets.
+    fn starts_map_literal(tokens: &[Token]) -> bool{
+        let mut depth = 0;
+        for token in &tokens[1..]{
+            match token.get_kind() {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket => depth -= 1,
+                TokenKind::RBrace if depth == 0 => return false,
+                TokenKind::RBrace => depth -= 1,
+                TokenKind::Semicolon if depth == 0 => return false,
+                TokenKind::Colon if depth == 0 => return true,
+                _ => {}
+            }
+        }
+        return false;
+    }
+
+    /// Parses `{ instructions }` opened by the `owner` keyword, starting at `tokens[0]`, and returns the index of the closing '}'.
+    fn parse_block(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<(Block, usize), CustomError>{
+        self.nested(tokens.first(), || self.parse_block_in(owner, tokens, context))
+    }
+
+    fn parse_block_in(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<(Block, usize), CustomError>{
+        if tokens.is_empty(){
+            return Err(CustomError::new_parser_error("'{' expected but none found"));
+        }
+        if !tokens[0].is(TokenKind::LBrace){
+            return Err(error_at("'{' expected but none found", &tokens[0]));
+        }
+        let end = self.get_end_of_block(tokens[1..].to_vec()).map_err(|e| label_block(locate_at(e, &tokens[0]), owner))? + 1;
+        let instructions = self.parse_instructions_in(tokens[1..end].to_vec(), Some(&tokens[end]), context)?;
+        return Ok((instructions, end));
+    }
+
+    /// Parses everything following the `owner` `if` keyword, including any `else if` / `else` continuation.
+    fn parse_condition_block(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<Stmt, CustomError>{
+        self.nested(Some(owner), || self.parse_condition_block_in(owner, tokens, context))
+    }
+
+    fn parse_condition_block_in(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<Stmt, CustomError>{
+        let (condition, instructions, end) = self.parse_condition_and_block(owner, tokens, context)?;
+        let rest = &tokens[end + 1..];
+        if rest.is_empty(){
+            return Ok(Stmt::If{condition, then_block: instructions, else_branch: None});
+        }
+        if !rest[0].is(TokenKind::Keyword(Kw::Else)){
+            return Err(error_at(&format!("Unexpected token after '}}': {}", rest[0].get_value()), &rest[0]).with_note(BLOCK_SEPARATOR_NOTE));
+        }
+        if rest.len() > 1 && rest[1].is(TokenKind::Keyword(Kw::If)){
+            let else_if = self.parse_condition_block(&rest[1], &rest[2..], context)?;
+            return Ok(Stmt::If{condition, then_block: instructions, else_branch: Some(Box::new(else_if))});
+        }
+        if rest.len() == 1 || !rest[1].is(TokenKind::LBrace){
+            return Err(error_at("Expected { after else", &rest[0]));
+        }
+        let (else_block, else_end) = self.parse_block(&rest[0], &rest[1..], context)?;
+        if else_end + 1 < rest.len() - 1{
+            return Err(error_at(&format!("Unexpected token after '}}': {}", rest[else_end + 2].get_value()), &rest[else_end + 2]).with_note(BLOCK_SEPARATOR_NOTE));
+        }
+        return Ok(Stmt::If{condition, then_block: instructions, else_branch: Some(Box::new(Stmt::Block(else_block)))});
+    }
+
+    /// Parses `for variable in iterable { instructions }`, or `for key, value in map { instructions }`.
+    fn parse_for(&self, tokens: &[Token], context: Context) -> Result<Stmt, CustomError>{
+        let mut variables: Vec<String> = Vec::new();
+        let mut i = 1;
+        loop{
+            if i >= tokens.len() || !tokens[i].is(TokenKind::Identifier){
+                return Err(error_at("Loop variable expected after 'for'", &tokens[i.min(tokens.len()) - 1]));
+            }
+            if variables.iter().any(|variable| variable == tokens[i].get_value()){
+                return Err(error_at(&format!("Duplicate loop variable {}", tokens[i].get_value()), &tokens[i]));
+            }
+            variables.push(tokens[i].get_value().to_string());
+            i += 1;
+            if variables.len() == 2 || i >= tokens.len() || !tokens[i].is(TokenKind::Comma){
+                break;
+            }
+            i += 1;
+        }
+        if i >= tokens.len() || !tokens[i].is(TokenKind::Keyword(Kw::In)){
+            return Err(error_at("'in' expected after the loop variables", &tokens[i - 1]));
+        }
+        let (iterable, body, end) = self.parse_condition_and_block(&tokens[0], &tokens[i + 1..], context.in_loop())?;
+        let end = end + i + 1;
+        if end < tokens.len() - 1{
+            return Err(error_at(&format!("Unexpected token after '}}': {}", tokens[end + 1].get_value()), &tokens[end + 1]).with_note(BLOCK_SEPARATOR_NOTE));
+        }
+        return Ok(Stmt::For{variables, iterable, body, span: span_of(&tokens[..i])});
+    }
+
+    /// Parses `try { instructions } catch error { instructions } finally { instructions }`, either of the `catch` and
+    /// `finally` blocks being optional but not both.
+    fn parse_try(&self, tokens: &[Token], context: Context) -> Result<Stmt, CustomError>{
+        let (body, end) = self.parse_block(&tokens[0], &tokens[1..], context)?;
+        let mut i = end + 2;
+        let mut catch = None;
+        if i < tokens.len() && tokens[i].is(TokenKind::Keyword(Kw::Catch)){
+            if i + 1 >= tokens.len() || !tokens[i + 1].is(TokenKind::Identifier){
+                return Err(error_at("Error variable expected after 'catch'", &tokens[i]));
+            }
+            let (handler, end) = self.parse_block(&tokens[i], &tokens[i + 2..], context)?;
+            catch = Some((tokens[i + 1].get_value().to_string(), handler));
+            i += end + 3;
+        }
+        let mut finally = None;
+        if i < tokens.len() && tokens[i].is(TokenKind::Keyword(Kw::Finally)){
+            let (block, end) = self.parse_block(&tokens[i], &tokens[i + 1..], context)?;
+            finally = Some(block);
+            i += end + 2;
+        }
+        if catch.is_none() && finally.is_none(){
+            return Err(label_block(error_at("'catch' or 'finally' expected after the 'try' block", &tokens[end + 1]), &tokens[0]));
+        }
+        if i < tokens.len(){
+            return Err(error_at(&format!("Unexpected token after '}}': {}", tokens[i].get_value()), &tokens[i]).with_note(BLOCK_SEPARATOR_NOTE));
+        }
+        return Ok(Stmt::Try{body, catch, finally});
+    }
+
+    /// Parses a function declaration, `tokens` starting right after the `owner` `fn` keyword.
+    fn parse_function(&self, owner: &Token, tokens: &[Token], context: Context) -> Result<Stmt, CustomError>{
+        if tokens.is_empty() || !tokens[0].is(TokenKind::Identifier){
+            return Err(error_at("Function name expected after 'fn'", owner));
+        }
+        let name = tokens[0].get_value();
+        if tokens.len() < 2 || !tokens[1].is(TokenKind::LParen){
+            return Err(error_at(&format!("'(' expected after function name {}", name), &tokens[0]));
+        }
+        let mut parameters: Vec<String> = Vec::new();
+        let mut i = 2;
+        loop{
+            if i >= tokens.len(){
+                return Err(error_at("')' expected but none found", &tokens[1]));
+            }
+            if tokens[i].is(TokenKind::RParen){
+                break;
+            }
+            if !tokens[i].is(TokenKind::Identifier){
+                return Err(error_at(&format!("Parameter name expected but found {}", tokens[i].get_value()), &tokens[i]));
+            }
+            if parameters.iter().any(|parameter| parameter == tokens[i].get_value()){
+                return Err(error_at(&format!("Duplicate parameter {} in function {}", tokens[i].get_value(), name), &tokens[i]));
+            }
+            parameters.push(tokens[i].get_value().to_string());
+            i += 1;
+            if i < tokens.len() && tokens[i].is(TokenKind::Comma){
+                i += 1;
+            }else if i < tokens.len() && !tokens[i].is(TokenKind::RParen){
+                return Err(error_at(&format!("',' or ')' expected but found {}", tokens[i].get_value()), &tokens[i]));
+            }
+        }
+        let (body, end) = self.parse_block(owner, &tokens[i + 1..], context.in_function())?;
+        let end = end + i + 1;
+        if end < tokens.len() - 1{
+            return Err(error_at(&format!("Unexpected token after '}}': {}", tokens[end + 1].get_value()), &tokens[end + 1]).with_note(BLOCK_SEPARATOR_NOTE));
+        }
+        let mut definition = FunctionDef{name: name.to_string(), parameters, body, span: span_of(&[owner.clone(), tokens[0].clone()])};
+        Resolver::resolve_function(&mut definition);
+        return Ok(Stmt::Function(Rc::new(definition), Slot::Global));
+    }
+
+    /// Drops the end of input marker, statements being delimited by `;` instead.
+    fn without_eof(mut tokens: Vec<Token>) -> Vec<Token>{
+        tokens.retain(|token| !token.is(TokenKind::Eof));
+        tokens
+    }
+
+    pub fn parse_instructions(&self, tokens: Vec<Token>) -> Result<Vec<Stmt>, CustomError>{
+        let instructions = self.parse_instructions_in(Self::without_eof(tokens), None, Context::TOP_LEVEL)?;
+        return Ok(instructions);
+    }
+
+    /// Parses every statement it can, skipping bad ones up to the next `;` or `}`, and returns the errors met along the way.
+    pub fn parse_instructions_with_diagnostics(&self, tokens: Vec<Token>) -> (Vec<Stmt>, Vec<CustomError>){
+        let context = Context{recovering: true, ..Context::TOP_LEVEL};
+        let instructions = match self.parse_instructions_in(Self::without_eof(tokens), None, context) {
+            Ok(instructions) => instructions,
+            Err(error) => {
+                self.diagnostics.borrow_mut().push(error);
+                Vec::new()
+            }
+        };
+        let mut diagnostics = std::mem::take(&mut *self.diagnostics.borrow_mut());
+        diagnostics.sort_by_key(|error| error.get_span().map(|span| span.get_start()));
+        return (instructions, diagnostics);
+    }
+
+    /// Fails on the first bad statement, or records it and moves on to the next one when recovering.
+    fn report(&self, error: CustomError, context: Context) -> Result<(), CustomError>{
+        if !context.recovering{
+            return Err(error);
+        }
+        self.diagnostics.borrow_mut().push(error);
+        return Ok(());
+    }
+
+    /// Parses the statements of `tokens`, `end` being the token that follows them such as the '}' closing a block.
+    fn parse_instructions_in(&self, tokens: Vec<Token>, end: Option<&Token>, context: Context) -> Result<Vec<Stmt>, CustomError>{
         let mut instructions = Vec::new();
         let mut i = 0;
-        let mut bracket_count = 0;
         while i < tokens.len(){
             let mut j = i;
-            while j < tokens.len() && (!tokens[j].equals(&Token::new_semicolon()) || bracket_count != 0){
-                if tokens[j].equals(&Token::new_bracket("{")){
+            let mut bracket_count = 0;
+            let mut stray_bracket = false;
+            while j < tokens.len() && (!tokens[j].is(TokenKind::Semicolon) || bracket_count != 0){
+                if tokens[j].is(TokenKind::LBrace){
                     bracket_count += 1;
-                }else if tokens[j].equals(&Token::new_bracket("}")){
+                }else if tokens[j].is(TokenKind::RBrace){
+                    if bracket_count == 0{
+                        stray_bracket = true;
+                        break;
+                    }
                     bracket_count -= 1;
                 }
                 j += 1;
             }
-            let part = tokens[i..j].to_vec();
-            let instruction = self.parse(part);
-            match instruction{
-                Ok(instruction) => instructions.push(instruction),
-                Err(error) => return Err(error),
+            if j > i || !stray_bracket{
+                let part = tokens[i..j].to_vec();
+                match self.parse_in(part, tokens.get(j).or(end), context){
+                    Ok(instruction) => instructions.push(instruction),
+                    Err(error) => self.report(error, context)?,
+                }
+            }
+            if stray_bracket{
+                self.report(error_at("Unexpected token: }", &tokens[j]).with_note("this '}' does not close any block"), context)?;
             }
             i = j + 1;
         }
@@ -108,98 +659,127 @@ impl Parser{
     }
 
 
-    pub fn parse(&self, tokens: Vec<Token>) -> Result<Box<dyn Instruction>, CustomError>{
-        if tokens.len() == 0{
+    pub fn parse(&self, tokens: Vec<Token>) -> Result<Stmt, CustomError>{
+        return self.parse_in(Self::without_eof(tokens), None, Context::TOP_LEVEL);
+    }
+
+    /// Parses one statement. Errors raised without a location come from running out of tokens, so they point at the
+    /// `end` token following the statement, or at its first token when nothing follows it.
+    fn parse_in(&self, tokens: Vec<Token>, end: Option<&Token>, context: Context) -> Result<Stmt, CustomError>{
+        let end = end.or(tokens.first()).cloned();
+        match self.parse_statement(tokens, context) {
+            Ok(instruction) => return Ok(instruction),
+            Err(error) => match end {
+                Some(token) => return Err(locate_at(error, &token)),
+                None => return Err(error),
+            },
+        }
+    }
+
+    fn parse_statement(&self, tokens: Vec<Token>, context: Context) -> Result<Stmt, CustomError>{
+        if tokens.is_empty(){
             return Err(CustomError::new_parser_error("No tokens to parse"));
         }
-        if tokens[0].get_token_type() == Token::new_identifier("").get_token_type(){
+        if tokens[0].is(TokenKind::Identifier){
             let variable = tokens[0].get_value();
-            if tokens.len() > 1 && tokens[1].equals(&Token::new_assign("=")){
+            if tokens.len() > 1 && tokens[1].is(TokenKind::Assign){
                 if tokens.len() < 3{
                     return Err(CustomError::new_parser_error("Value expected but none found"));
                 }
-                let result = self.get_valuable(tokens[2..].to_vec());
+                let result = self.get_expression(tokens[2..].to_vec());
                 match result{
-                    Ok(value) => return Ok(Box::new( Affectation::new(&variable.to_string(), value))),
+                    Ok(value) => return Ok(Stmt::Assign{name: variable.to_string(), value, span: span_of(&tokens), slot: Slot::Global}),
                     Err(error) => return Err(error),
                 }
-            }else {
-                return Ok(Box::new(Variable::new(variable)));
             }
         }
         
-        if tokens[0].equals(&Token::new_keyword("while") ) || tokens[0].equals(&Token::new_keyword("if")){
-            let tks = tokens[1..].to_vec();
-            let mut i = 0;
-            while i < tks.len() && !tks[i].equals(&Token::new_bracket("{")){
-                i += 1;
+        if tokens[0].is(TokenKind::Keyword(Kw::While)){
+            let (condition, instructions, end) = self.parse_condition_and_block(&tokens[0], &tokens[1..], context.in_loop())?;
+            if end + 1 < tokens.len() - 1{
+                return Err(error_at(&format!("Unexpected token after '}}': {}", tokens[end + 2].get_value()), &tokens[end + 2]).with_note(BLOCK_SEPARATOR_NOTE));
             }
-            if i == tks.len(){
-                return Err(CustomError::new_parser_error("'{' expected but none found"));
-            }
-            let condition;
-            match self.get_valuable(tks[0..i].to_vec()){
-                Ok(c) => condition = c,
-                Err(error) => return Err(error),
-            }
-            let instructions;
-            let mut j;
-            match self.get_end_of_block(tks[i+1..].to_vec()){
-                Ok(k) => {
-                    j = k+1+i;
-                    match self.parse_instructions(tks[i+1..j].to_vec()){
-                        Ok(its) => instructions = its,
-                        Err(error) => return Err(error),
-                    }
-                }
-                Err(error) => return Err(error),
+            return Ok(Stmt::While{condition, body: instructions});
+        }
+
+        if tokens[0].is(TokenKind::Keyword(Kw::For)){
+            return self.parse_for(&tokens, context);
+        }
+
+        if tokens[0].is(TokenKind::Keyword(Kw::If)){
+            return self.parse_condition_block(&tokens[0], &tokens[1..], context);
+        }
+
+        if tokens[0].is(TokenKind::Keyword(Kw::Fn)){
+            return self.parse_function(&tokens[0], &tokens[1..], context);
+        }
+
+        if tokens[0].is(TokenKind::Keyword(Kw::Return)){
+            if !context.in_function{
+                return Err(error_at("'return' outside of a function", &tokens[0]).with_note("'return' can only be used inside the body of a 'fn'"));
             }
-            if tokens[0].equals(&Token::new_keyword("while")){
-                if j < tks.len()-1{
-                    return Err(CustomError::new_parser_error(&format!("Unexpected token after '}}': {}", tokens[j+1].get_value())));
-                }
-                return Ok(Box::new(ConditionLoop::new(condition, InstructionBlock::new(instructions))));
-            }else{
-                if j < tks.len()-1{
-                    if tks[j+1].equals(&Token::new_keyword("else")){
-                        j += 1;
-                        if j < tks.len()-1{
-                            j += 1;
-                            let instructions2;
-                            let k;
-                            match self.get_end_of_block(tks[j+1..].to_vec()){
-                                Ok(l) => {
-                                    k = l+1+j;
-                                    match self.parse_instructions(tks[j+1..k].to_vec()){
-                                        Ok(its) => instructions2 = its,
-                                        Err(error) => return Err(error),
-                                    }
-                                }
-                                Err(error) => return Err(error),
-                            }
-                            if k < tks.len()-1{
-                                return Err(CustomError::new_parser_error(&format!("Unexpected token after '}}': {}", tokens[k+1].get_value())));
-                            }
-                            return Ok(Box::new(ConditionBlock::new_with_else(condition, InstructionBlock::new(instructions), InstructionBlock::new(instructions2))));
-                        }else{
-                            return Err(CustomError::new_parser_error("Expected { after else"));
-                        }
-                    }else{
-                        return Err(CustomError::new_parser_error(&format!("Unexpected token after '}}': {}", tokens[j+1].get_value())));
-                    }
-                }
-                return Ok(Box::new(ConditionBlock::new(condition, InstructionBlock::new(instructions))));
+            if tokens.len() == 1{
+                return Ok(Stmt::Return{value: Expr::Null, span: span_of(&tokens)});
             }
+            return Ok(Stmt::Return{value: self.get_expression(tokens[1..].to_vec())?, span: span_of(&tokens)});
         }
-        
-        match self.get_valuable(tokens.clone()){
-            Ok(v) => {
-                let instruction: Box<dyn Instruction> = Box::new(v);
-                return Ok(instruction);
+
+        if tokens[0].is(TokenKind::Keyword(Kw::Try)){
+            return self.parse_try(&tokens, context);
+        }
+
+        if tokens[0].is(TokenKind::Keyword(Kw::Throw)){
+            if tokens.len() == 1{
+                return Err(CustomError::new_parser_error("Value expected after 'throw'"));
             }
-            Err(_) => {}
+            return Ok(Stmt::Throw{value: self.get_expression(tokens[1..].to_vec())?, span: span_of(&tokens)});
         }
-        return Err(CustomError::new_parser_error(&format!("Unexpected token: {}", tokens[0].get_value())));
+
+        if tokens[0].is(TokenKind::Keyword(Kw::Break)) || tokens[0].is(TokenKind::Keyword(Kw::Continue)){
+            let keyword = tokens[0].get_value();
+            if !context.in_loop{
+                return Err(error_at(&format!("'{}' outside of a loop", keyword), &tokens[0]).with_note(&format!("'{}' can only be used inside the body of a 'while' or 'for' loop", keyword)));
+            }
+            if tokens.len() > 1{
+                return Err(error_at(&format!("Unexpected token after '{}': {}", keyword, tokens[1].get_value()), &tokens[1]));
+            }
+            if tokens[0].is(TokenKind::Keyword(Kw::Break)){
+                return Ok(Stmt::Break);
+            }
+            return Ok(Stmt::Continue);
+        }
+
+        if let Some(assign) = Self::find_assignment(&tokens){
+            return self.parse_index_assignment(&tokens, assign);
+        }
+
+        return Ok(Stmt::Expr(self.get_expression(tokens)?));
+    }
+
+    /// Position of the `=` of an assignment statement, outside of any parentheses, brackets or braces.
+    fn find_assignment(tokens: &[Token]) -> Option<usize>{
+        let mut depth = 0;
+        for (i, token) in tokens.iter().enumerate(){
+            match token.get_kind() {
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth -= 1,
+                TokenKind::Assign if depth == 0 => return Some(i),
+                _ => {}
+            }
+        }
+        return None;
     }
 
-}
\ No newline at end of file
+    /// Parses `target[index] = value`, the `=` being `tokens[assign]`.
+    fn parse_index_assignment(&self, tokens: &[Token], assign: usize) -> Result<Stmt, CustomError>{
+        if assign + 1 == tokens.len(){
+            return Err(CustomError::new_parser_error("Value expected but none found"));
+        }
+        let value = self.get_expression(tokens[assign + 1..].to_vec())?;
+        match self.get_expression(tokens[..assign].to_vec()).map_err(|e| locate_at(e, &tokens[assign]))? {
+            Expr::Index{target, index, ..} => return Ok(Stmt::SetIndex{target: *target, index: *index, value, span: span_of(tokens)}),
+            target => return Err(error_at(&format!("Cannot assign to {}", target), &tokens[assign])),
+        }
+    }
+
+}
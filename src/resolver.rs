@@ -0,0 +1,173 @@
+use crate::ast::{Block, Expr, FunctionDef, Slot, Stmt};
+use crate::stack;
+use crate::variables::VariableManager;
+
+/// Assigns every variable a slot, so that reads and writes index vectors instead of looking names up.
+///
+/// A variable belongs to the innermost scope of its function where it is assigned first, an assignment in a nested
+/// block updating the variable of an enclosing scope assigned earlier. The variables of the top level are the global
+/// ones, given an index by the interpreter running the program so that a name keeps its index from one statement to
+/// the next.
+///
+/// A function only sees its own scopes and the global variables, so function bodies are resolved on their own when
+/// the function is created, the top-level statements in turn not going down into the functions they declare. As a
+/// function is created before the program running it is known, it looks the global variables up by name.
+pub struct Resolver<'a> {
+    /// Names given a slot in each scope of the function being resolved, the index of a name being its slot.
+    scopes: Vec<Vec<String>>,
+    /// Global variables of the program, when resolving its top-level statements.
+    globals: Option<&'a mut VariableManager>,
+}
+
+impl Resolver<'_> {
+    /// Resolves a top-level statement run with `variables`, its blocks opening the first scopes of the program.
+    pub(crate) fn resolve(stmt: &mut Stmt, variables: &mut VariableManager) {
+        let mut resolver = Resolver{scopes: Vec::new(), globals: Some(variables)};
+        resolver.resolve_stmt(stmt);
+    }
+
+    /// Resolves the body of a function, run in a block nested in the scope holding the parameters.
+    pub fn resolve_function(definition: &mut FunctionDef) {
+        let mut resolver = Resolver{scopes: vec![definition.parameters.clone()], globals: None};
+        resolver.resolve_block(&mut definition.body);
+    }
+
+    /// Slot of the variable `name` at this point, a variable only existing in a scope once it has been assigned in it.
+    fn lookup(&self, name: &str) -> Slot {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            // The last parameter wins when a function built by hand repeats a name, as its argument is declared last.
+            if let Some(index) = scope.iter().rposition(|existing| existing == name) {
+                return Slot::Local(depth, index);
+            }
+        }
+        match self.globals.as_ref().and_then(|globals| globals.find_global(name)) {
+            Some(index) => Slot::GlobalIndex(index),
+            None => Slot::Global,
+        }
+    }
+
+    /// Slot assigned by `name = ...`: the variable already visible, or else a new one in the innermost scope.
+    fn declare(&mut self, name: &str) -> Slot {
+        let slot = self.lookup(name);
+        if slot != Slot::Global {
+            return slot;
+        }
+        match (self.scopes.last_mut(), &mut self.globals) {
+            (Some(scope), _) => {
+                scope.push(name.to_string());
+                Slot::Local(0, scope.len() - 1)
+            },
+            (None, Some(globals)) => Slot::GlobalIndex(globals.declare_global(name)),
+            (None, None) => Slot::Global,
+        }
+    }
+
+    fn resolve_block(&mut self, block: &mut Block) {
+        stack::guard(|| self.resolve_block_in_place(block))
+    }
+
+    fn resolve_block_in_place(&mut self, block: &mut Block) {
+        self.scopes.push(Vec::new());
+        for stmt in block {
+            self.resolve_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => self.resolve_expr(expr),
+            Stmt::Assign{name, value, slot, ..} => {
+                self.resolve_expr(value);
+                *slot = self.declare(name);
+            },
+            Stmt::SetIndex{target, index, value, ..} => {
+                self.resolve_expr(target);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            },
+            Stmt::Block(block) => self.resolve_block(block),
+            Stmt::If{condition, then_block, else_branch} => {
+                self.resolve_expr(condition);
+                self.resolve_block(then_block);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            },
+            Stmt::While{condition, body} => {
+                self.resolve_expr(condition);
+                self.resolve_block(body);
+            },
+            Stmt::For{variables, iterable, body, ..} => {
+                self.resolve_expr(iterable);
+                // Each iteration opens a scope holding the loop variables, around the scope of the body.
+                self.scopes.push(variables.clone());
+                self.resolve_block(body);
+                self.scopes.pop();
+            },
+            Stmt::Function(definition, slot) => *slot = self.declare(&definition.name),
+            Stmt::Return{value, ..} => self.resolve_expr(value),
+            Stmt::Break | Stmt::Continue => (),
+            Stmt::Throw{value, ..} => self.resolve_expr(value),
+            Stmt::Try{body, catch, finally} => {
+                self.resolve_block(body);
+                if let Some((variable, handler)) = catch {
+                    // Like a loop variable, the error is stored in a scope of its own around the scope of the handler.
+                    self.scopes.push(vec![variable.clone()]);
+                    self.resolve_block(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.resolve_block(finally);
+                }
+            },
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        stack::guard(|| self.resolve_expr_in_place(expr))
+    }
+
+    fn resolve_expr_in_place(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Integer(_) | Expr::Float(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Null => (),
+            Expr::Variable{name, slot, ..} => *slot = self.lookup(name),
+            Expr::Binary{left, right, ..} | Expr::Comparison{left, right, ..} | Expr::Logical{left, right, ..} => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            },
+            Expr::Unary{operand, ..} => self.resolve_expr(operand),
+            Expr::Range{start, end, ..} => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+            },
+            Expr::Call{callee, arguments, ..} => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            },
+            Expr::List{elements, ..} => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            },
+            Expr::Map{entries, ..} => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            },
+            Expr::Index{target, index, ..} => {
+                self.resolve_expr(target);
+                self.resolve_expr(index);
+            },
+            Expr::Slice{target, start, end, ..} => {
+                self.resolve_expr(target);
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_expr(bound);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::errors::CustomError;
+use crate::span::Span;
+use crate::value::Value;
+
+/// Points `error` at the source of the node or instruction that raised it, when it was built from source.
+pub fn locate(error: CustomError, span: &Option<Span>) -> CustomError {
+    match span {
+        Some(span) => error.with_span(*span),
+        None => error,
+    }
+}
+
+/// Operand of `and`, `or` or `not`, which must be a boolean.
+pub fn get_boolean(value: Value, span: &Option<Span>) -> Result<bool, CustomError> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        value => Err(locate(CustomError::new_operation_error(&format!("Expected a boolean in logical operation but found {:?}", value)), span)),
+    }
+}
+
+/// Conditions of `if` and `while` only hold for `true`, any other value counting as false.
+pub fn is_true(value: Value) -> bool {
+    matches!(value, Value::Boolean(true))
+}
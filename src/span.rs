@@ -0,0 +1,45 @@
+use std::fmt;
+use std::fmt::Display;
+
+/// Location of a piece of source code.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Span {
+    /// `start` and `end` are byte offsets, `line` and `column` (counted in characters) are those of `start` and start at 1.
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span{start, end, line, column}
+    }
+
+    /// Smallest span covering both `self` and a later `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span{start: self.start, end: self.end.max(other.end), line: self.line, column: self.column}
+    }
+
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -0,0 +1,9 @@
+/// Stack left below which `guard` moves to a new segment, above what any step between two guards uses.
+const RED_ZONE: usize = 256 * 1024;
+const SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Runs `f`, on a new stack segment when the current one is nearly full, so that recursive functions and deeply
+/// nested programs cannot overflow the stack of the host.
+pub fn guard<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
@@ -1,26 +1,179 @@
-use std::fmt::Debug;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{Debug, Write};
+use std::rc::Rc;
 use crate::errors::CustomError;
+use crate::interpreter::Function;
+use crate::map::Entries;
+use crate::stack;
 
+#[derive(Clone)]
 pub enum Value {
     Integer(i64),
     Float(f64),
     String(String),
     Boolean(bool),
     Null(),
+    Function(Rc<Function>),
+    /// Shared between every variable holding it, so that a change made through one is seen through the others.
+    List(Rc<RefCell<Vec<Value>>>),
+    /// Entries in insertion order, shared like lists. Keys are compared with `Value::eq`, so `1` and `1.0` are the same key.
+    Map(Rc<RefCell<Entries>>),
+    /// Integers from the first bound included to the second excluded, produced one at a time by a `for` loop.
+    Range(i64, i64),
 }
 
 impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Value::Integer(i) => write!(f, "Integer({})", i),
-            Value::Float(fl) => write!(f, "Float({})", fl),
-            Value::String(s) => write!(f, "String({})", s),
-            Value::Boolean(b) => write!(f, "Boolean({})", b),
-            Value::Null() => write!(f, "Null"),
+        let mut out = String::new();
+        self.render(&mut out, Style::Debug, &mut HashSet::new());
+        f.write_str(&out)
+    }
+}
+
+/// Drops the lists and maps held by no other value one level at a time, as dropping them recursively would overflow
+/// the stack for a deeply nested value.
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_nested(&mut pending);
+        while let Some(mut value) = pending.pop() {
+            value.take_nested(&mut pending);
         }
     }
 }
 
+/// How `Value::render` writes a value.
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    /// With the type of every value, as shown in error messages.
+    Debug,
+    /// As `print` shows it.
+    Display,
+    /// As written inside a list or a map, strings being quoted.
+    Element,
+}
+
+/// Lists or maps being compared by the enclosing calls of `Value::eq`.
+type Comparing = HashSet<(*const (), *const ())>;
+
+/// Compares the contents of a pair of lists or maps with `f`, refusing a pair already being compared by an enclosing
+/// call as that only happens for values containing themselves.
+fn compare_contents(pair: (*const (), *const ()), comparing: &mut Comparing, f: impl FnOnce(&mut Comparing) -> Result<bool, CustomError>) -> Result<bool, CustomError> {
+    if !comparing.insert(pair) {
+        return Err(CustomError::new_operation_error("Cannot compare values containing themselves"));
+    }
+    let result = stack::guard(|| f(comparing));
+    comparing.remove(&pair);
+    result
+}
+
+/// Orders an integer and a float exactly, without the rounding of converting a large integer to a float.
+fn compare_integer_float(i: i64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    // 2^63 is the first float above every integer, and -2^63 the lowest integer.
+    if f >= 9223372036854775808.0 {
+        return Some(Ordering::Less);
+    }
+    if f < -9223372036854775808.0 {
+        return Some(Ordering::Greater);
+    }
+    let whole = f.trunc();
+    match i.cmp(&(whole as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(f - whole)),
+        ordering => Some(ordering),
+    }
+}
+
+/// Result of a checked integer operation, `None` meaning it does not fit in an integer.
+fn checked(result: Option<i64>) -> Result<Value, CustomError> {
+    match result {
+        Some(i) => Ok(Value::Integer(i)),
+        None => Err(CustomError::new_integer_overflow_error()),
+    }
+}
+
+/// `s` repeated `count` times, refusing counts that are negative or would not fit in memory.
+fn repeat(s: &str, count: i64) -> Result<Value, CustomError> {
+    if count < 0 {
+        return Err(CustomError::new_operation_error(&format!("Cannot repeat a string {} times", count)));
+    }
+    let too_long = || CustomError::new_operation_error(&format!("Cannot repeat a string {} times, the result is too long", count));
+    let length = s.len().checked_mul(count as usize).ok_or_else(too_long)?;
+    let mut result = String::new();
+    result.try_reserve_exact(length).map_err(|_| too_long())?;
+    while result.len() < length {
+        result.push_str(s);
+    }
+    Ok(Value::String(result))
+}
+
+/// Position of the list element `index`, negative indexes counting from the end.
+fn get_position(index: &Value, length: usize) -> Result<usize, CustomError> {
+    let i = match index {
+        Value::Integer(i) => *i,
+        _ => return Err(CustomError::new_type_error(&format!("List indexes must be integers, not {:?}", index))),
+    };
+    let position = if i < 0 { i + length as i64 } else { i };
+    if position < 0 || position >= length as i64 {
+        return Err(CustomError::new_index_error(i, length));
+    }
+    Ok(position as usize)
+}
+
+/// What a `for` loop goes through, read one element at a time so that a range is never stored as a list.
+enum Source {
+    Range(i64, i64),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Entries>>),
+    String(Vec<char>),
+}
+
+/// Iterator over the elements of a value, in the order a `for` loop gives them to its variables.
+///
+/// Lists and maps are read live at each step, so changes made by the loop body are seen by the next iterations.
+pub struct ValueIterator {
+    source: Source,
+    position: usize,
+}
+
+impl Iterator for ValueIterator {
+    /// The element, and for a map the value of the element being its key.
+    type Item = (Value, Option<Value>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = match &self.source {
+            Source::Range(start, end) => {
+                let i = start.checked_add(self.position as i64).filter(|i| i < end)?;
+                (Value::Integer(i), None)
+            },
+            Source::List(list) => (list.borrow().get(self.position)?.clone(), None),
+            Source::Map(map) => {
+                let map = map.borrow();
+                let (key, value) = map.get(self.position)?;
+                (key.clone(), Some(value.clone()))
+            },
+            Source::String(chars) => (Value::String(chars.get(self.position)?.to_string()), None),
+        };
+        self.position += 1;
+        Some(item)
+    }
+}
+
+/// Position of a slice bound, `null` standing for `default` and bounds beyond the list being clamped to it.
+fn get_bound(bound: &Value, length: usize, default: usize) -> Result<usize, CustomError> {
+    let i = match bound {
+        Value::Null() => return Ok(default),
+        Value::Integer(i) => *i,
+        _ => return Err(CustomError::new_type_error(&format!("Slice bounds must be integers, not {:?}", bound))),
+    };
+    let position = if i < 0 { i.saturating_add(length as i64) } else { i };
+    Ok(position.clamp(0, length as i64) as usize)
+}
+
 impl Value {
 
     pub fn new_string(s: &str) -> Value {
@@ -43,51 +196,202 @@ impl Value {
         Value::Null()
     }
 
+    pub fn new_list(values: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(values)))
+    }
+
+    /// Range from `start` included to `end` excluded, both bounds being integers.
+    pub fn new_range(start: &Value, end: &Value) -> Result<Value, CustomError> {
+        match (start, end) {
+            (Value::Integer(start), Value::Integer(end)) => Ok(Value::Range(*start, *end)),
+            _ => Err(CustomError::new_type_error(&format!("Range bounds must be integers, not {:?} and {:?}", start, end))),
+        }
+    }
+
+    /// Map of `entries`, a repeated key keeping its first position and its last value.
+    pub fn new_map(entries: Vec<(Value, Value)>) -> Result<Value, CustomError> {
+        let mut map = Entries::default();
+        for (key, value) in entries {
+            match map.find(&key)? {
+                Some(position) => map.set_value(position, value),
+                None => map.push(key, value),
+            }
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    /// Both operands as floats when one is an integer and the other a float, mixed arithmetic giving a float.
+    fn promote(&self, other: &Value) -> Option<(f64, f64)> {
+        match (self, other) {
+            (Value::Integer(a), Value::Float(b)) => Some((*a as f64, *b)),
+            (Value::Float(a), Value::Integer(b)) => Some((*a, *b as f64)),
+            _ => None,
+        }
+    }
+
     pub fn add(&self, other: &Value) -> Result<Value, CustomError> {
+        if let Some((a, b)) = self.promote(other) {
+            return Ok(Value::Float(a + b));
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
+            (Value::Integer(a), Value::Integer(b)) => checked(a.checked_add(*b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(*a || *b)),
+            (Value::List(a), Value::List(b)) => Ok(Value::new_list(a.borrow().iter().chain(b.borrow().iter()).map(Value::clone).collect())),
             _ => Err(CustomError::new_operation_error(format!("Cannot add {:?} and {:?}", self, other).as_str())),
         }
     }
 
     pub fn sub(&self, other: &Value) -> Result<Value, CustomError> {
+        if let Some((a, b)) = self.promote(other) {
+            return Ok(Value::Float(a - b));
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a - b)),
+            (Value::Integer(a), Value::Integer(b)) => checked(a.checked_sub(*b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
             _ => Err(CustomError::new_operation_error(format!("Cannot subtract {:?} and {:?}", self, other).as_str())),
         }
     }
 
     pub fn mul(&self, other: &Value) -> Result<Value, CustomError> {
+        if let Some((a, b)) = self.promote(other) {
+            return Ok(Value::Float(a * b));
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a * b)),
+            (Value::Integer(a), Value::Integer(b)) => checked(a.checked_mul(*b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(*a && *b)),
-            (Value::String(a), Value::Integer(b)) => Ok(Value::String(a.repeat(*b as usize))),
+            (Value::String(a), Value::Integer(b)) => repeat(a, *b),
             _ => Err(CustomError::new_operation_error(format!("Cannot multiply {:?} and {:?}", self, other).as_str())),
         }
     }
 
     pub fn div(&self, other: &Value) -> Result<Value, CustomError> {
+        if let Some((a, b)) = self.promote(other) {
+            return Ok(Value::Float(a / b));
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a / b)),
+            (Value::Integer(_), Value::Integer(0)) => Err(CustomError::new_division_by_zero_error()),
+            (Value::Integer(a), Value::Integer(b)) => checked(a.checked_div(*b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
             _ => Err(CustomError::new_operation_error(format!("Cannot divide {:?} and {:?}", self, other).as_str())),
         }
     }
 
-    pub fn eq(&self, other: &Value) -> Result<bool, CustomError> {
-        let result ;
+    /// Remainder of the division, with the sign of `self`.
+    pub fn rem(&self, other: &Value) -> Result<Value, CustomError> {
+        if let Some((a, b)) = self.promote(other) {
+            return Ok(Value::Float(a % b));
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => result = a == b,
-            (Value::Float(a), Value::Float(b)) => result = a == b,
-            (Value::String(a), Value::String(b)) => result = a == b,
-            (Value::Boolean(a), Value::Boolean(b)) => result = a == b,
-            _ => result = false,
+            (Value::Integer(_), Value::Integer(0)) => Err(CustomError::new_division_by_zero_error()),
+            // Every integer is a multiple of -1, even `i64::MIN` whose quotient by -1 overflows.
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a.wrapping_rem(*b))),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a % b)),
+            _ => Err(CustomError::new_operation_error(format!("Cannot compute the remainder of {:?} and {:?}", self, other).as_str())),
         }
+    }
+
+    pub fn neg(&self) -> Result<Value, CustomError> {
+        match self {
+            Value::Integer(a) => checked(a.checked_neg()),
+            Value::Float(a) => Ok(Value::Float(-a)),
+            _ => Err(CustomError::new_operation_error(format!("Cannot negate {:?}", self).as_str())),
+        }
+    }
+
+    pub fn not(&self) -> Result<Value, CustomError> {
+        match self {
+            Value::Boolean(a) => Ok(Value::Boolean(!a)),
+            _ => Err(CustomError::new_operation_error(format!("Cannot apply not to {:?}", self).as_str())),
+        }
+    }
+
+    /// Result of the arithmetic operator `operator`, one of `+ - * / %`.
+    pub fn binary(&self, operator: char, other: &Value) -> Result<Value, CustomError> {
+        match operator {
+            '+' => self.add(other),
+            '-' => self.sub(other),
+            '*' => self.mul(other),
+            '/' => self.div(other),
+            '%' => self.rem(other),
+            _ => Err(CustomError::new_operator_not_found_error(operator)),
+        }
+    }
+
+    /// Result of the unary operator `operator`, `-` or `!`.
+    pub fn unary(&self, operator: char) -> Result<Value, CustomError> {
+        match operator {
+            '-' => self.neg(),
+            '!' => self.not(),
+            _ => Err(CustomError::new_operator_not_found_error(operator)),
+        }
+    }
+
+    /// Result of the comparison operator `operator`, one of `> < ≥ ≤ = ≠`.
+    pub fn compare(&self, operator: char, other: &Value) -> Result<bool, CustomError> {
+        match operator {
+            '>' => self.gt(other),
+            '<' => self.lt(other),
+            '≥' => self.ge(other),
+            '≤' => self.le(other),
+            '=' => self.eq(other),
+            '≠' => self.neq(other),
+            _ => Err(CustomError::new_operator_not_found_error(operator)),
+        }
+    }
+
+    pub fn eq(&self, other: &Value) -> Result<bool, CustomError> {
+        self.eq_in(other, &mut HashSet::new())
+    }
+
+    fn eq_in(&self, other: &Value, comparing: &mut Comparing) -> Result<bool, CustomError> {
+        let result = match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            // A list or a map is equal to itself, without comparing the elements which may contain it.
+            (Value::List(a), Value::List(b)) if Rc::ptr_eq(a, b) => true,
+            (Value::Map(a), Value::Map(b)) if Rc::ptr_eq(a, b) => true,
+            (Value::List(a), Value::List(b)) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                let (a, b) = (a.borrow(), b.borrow());
+                if a.len() != b.len() {
+                    return Ok(false);
+                }
+                compare_contents(pair, comparing, |comparing| {
+                    for (x, y) in a.iter().zip(b.iter()) {
+                        if !x.eq_in(y, comparing)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                })?
+            },
+            (Value::Range(a, b), Value::Range(c, d)) => a == c && b == d,
+            (Value::Map(a), Value::Map(b)) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                let (a, b) = (a.borrow(), b.borrow());
+                if a.len() != b.len() {
+                    return Ok(false);
+                }
+                compare_contents(pair, comparing, |comparing| {
+                    // The order of the entries does not matter.
+                    for (key, x) in a.iter() {
+                        match b.find(key)?.and_then(|position| b.get(position)) {
+                            Some((_, y)) if x.eq_in(y, comparing)? => (),
+                            _ => return Ok(false),
+                        }
+                    }
+                    Ok(true)
+                })?
+            },
+            (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => compare_integer_float(*a, *b) == Some(Ordering::Equal),
+            _ => false,
+        };
         Ok(result)
     }
 
@@ -98,41 +402,205 @@ impl Value {
         }
     }
 
-    pub fn gt(&self, other: &Value) -> Result<bool, CustomError> {
+    /// Orders two numbers of any type, `None` when a float is NaN.
+    fn order(&self, other: &Value) -> Result<Option<Ordering>, CustomError> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(a > b),
-            (Value::Float(a), Value::Float(b)) => Ok(a > b),
+            (Value::Integer(a), Value::Integer(b)) => Ok(Some(a.cmp(b))),
+            (Value::Float(a), Value::Float(b)) => Ok(a.partial_cmp(b)),
+            (Value::Integer(a), Value::Float(b)) => Ok(compare_integer_float(*a, *b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(compare_integer_float(*b, *a).map(Ordering::reverse)),
             _ => Err(CustomError::new_operation_error(format!("Cannot compare {:?} and {:?}", self, other).as_str())),
         }
     }
 
+    pub fn gt(&self, other: &Value) -> Result<bool, CustomError> {
+        Ok(self.order(other)? == Some(Ordering::Greater))
+    }
+
     pub fn lt(&self, other: &Value) -> Result<bool, CustomError> {
-        match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(a < b),
-            (Value::Float(a), Value::Float(b)) => Ok(a < b),
-            _ => Err(CustomError::new_operation_error(format!("Cannot compare {:?} and {:?}", self, other).as_str())),
+        Ok(self.order(other)? == Some(Ordering::Less))
+    }
+
+    pub fn ge(&self, other: &Value) -> Result<bool, CustomError> {
+        Ok(matches!(self.order(other)?, Some(Ordering::Greater | Ordering::Equal)))
+    }
+
+    pub fn le(&self, other: &Value) -> Result<bool, CustomError> {
+        Ok(matches!(self.order(other)?, Some(Ordering::Less | Ordering::Equal)))
+    }
+
+
+    /// Iterator over the elements of the value for a `for` loop with `variables` variables, two being only allowed for
+    /// the keys and values of a map.
+    pub fn iterate(&self, variables: usize) -> Result<ValueIterator, CustomError> {
+        let source = match self {
+            Value::Range(start, end) => Source::Range(*start, *end),
+            Value::List(list) => Source::List(Rc::clone(list)),
+            Value::Map(map) => Source::Map(Rc::clone(map)),
+            Value::String(s) => Source::String(s.chars().collect()),
+            _ => return Err(CustomError::new_type_error(&format!("Cannot iterate over {:?}", self))),
+        };
+        if variables > 1 && !matches!(source, Source::Map(_)) {
+            return Err(CustomError::new_type_error(&format!("Cannot iterate over {:?} with {} variables, only maps give a key and a value", self, variables)));
         }
+        Ok(ValueIterator{source, position: 0})
     }
 
+    /// Element `index` of a list, or value of the key `index` of a map.
+    pub fn get_index(&self, index: &Value) -> Result<Value, CustomError> {
+        match self {
+            Value::List(list) => {
+                let list = list.borrow();
+                Ok(list[get_position(index, list.len())?].clone())
+            },
+            Value::Map(map) => {
+                let map = map.borrow();
+                match map.find(index)?.and_then(|position| map.get(position)) {
+                    Some((_, value)) => Ok(value.clone()),
+                    None => Err(CustomError::new_key_error(&index.to_element_string())),
+                }
+            },
+            _ => Err(CustomError::new_type_error(&format!("Cannot index {:?}", self))),
+        }
+    }
 
-    pub fn clone(&self) -> Value {
+    /// Replaces the element `index` of a list by `value`, or sets the key `index` of a map, adding it if needed.
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<(), CustomError> {
         match self {
-            Value::Integer(i) => Value::Integer(*i),
-            Value::Float(f) => Value::Float(*f),
-            Value::String(s) => Value::String(s.clone()),
-            Value::Boolean(b) => Value::Boolean(*b),
-            Value::Null() => Value::Null(),
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let position = get_position(index, list.len())?;
+                list[position] = value;
+                Ok(())
+            },
+            Value::Map(map) => {
+                // The key is looked up before borrowing the map mutably, as comparing keys may borrow the map itself.
+                let position = map.borrow().find(index)?;
+                let mut map = map.borrow_mut();
+                match position {
+                    Some(position) => map.set_value(position, value),
+                    None => map.push(index.clone(), value),
+                }
+                Ok(())
+            },
+            _ => Err(CustomError::new_type_error(&format!("Cannot assign to an index of {:?}", self))),
         }
     }
 
-    pub fn _to_string(&self) -> String {
+    /// New list of the elements from `start` included to `end` excluded, either bound being `null` to reach the edge.
+    pub fn slice(&self, start: &Value, end: &Value) -> Result<Value, CustomError> {
         match self {
-            Value::Integer(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::String(s) => s.clone(),
-            Value::Boolean(b) => b.to_string(),
-            Value::Null() => "None".to_string(),
+            Value::List(list) => {
+                let list = list.borrow();
+                let start = get_bound(start, list.len(), 0)?;
+                let end = get_bound(end, list.len(), list.len())?;
+                if start >= end {
+                    return Ok(Value::new_list(Vec::new()));
+                }
+                Ok(Value::new_list(list[start..end].iter().map(Value::clone).collect()))
+            },
+            _ => Err(CustomError::new_type_error(&format!("Cannot slice {:?}", self))),
         }
     }
 
-}
\ No newline at end of file
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Null() => "null",
+            Value::Function(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Range(..) => "range",
+        }
+    }
+
+    pub fn _to_string(&self) -> String {
+        let mut out = String::new();
+        self.render(&mut out, Style::Display, &mut HashSet::new());
+        out
+    }
+
+    /// The value as written inside a list or a map, strings being quoted.
+    fn to_element_string(&self) -> String {
+        let mut out = String::new();
+        self.render(&mut out, Style::Element, &mut HashSet::new());
+        out
+    }
+
+    /// Writes the value to `out`, a list or a map already in `rendering` being written `[...]` or `{...}` as it
+    /// contains itself.
+    fn render(&self, out: &mut String, style: Style, rendering: &mut HashSet<*const ()>) {
+        let inner = if style == Style::Debug { Style::Debug } else { Style::Element };
+        match (self, style) {
+            (Value::Integer(i), Style::Debug) => write!(out, "Integer({})", i),
+            (Value::Integer(i), _) => write!(out, "{}", i),
+            (Value::Float(f), Style::Debug) => write!(out, "Float({})", f),
+            (Value::Float(f), _) => write!(out, "{:?}", f),
+            (Value::String(s), Style::Debug) => write!(out, "String({})", s),
+            (Value::String(s), Style::Display) => write!(out, "{}", s),
+            (Value::String(s), Style::Element) => write!(out, "\"{}\"", s),
+            (Value::Boolean(b), Style::Debug) => write!(out, "Boolean({})", b),
+            (Value::Boolean(b), _) => write!(out, "{}", b),
+            (Value::Null(), Style::Debug) => write!(out, "Null"),
+            (Value::Null(), _) => write!(out, "null"),
+            (Value::Function(function), Style::Debug) => write!(out, "Function({})", function.get_name()),
+            (Value::Function(function), _) => write!(out, "<function {}>", function.get_name()),
+            (Value::List(list), _) => {
+                let pointer = Rc::as_ptr(list) as *const ();
+                out.push_str(if style == Style::Debug { "List([" } else { "[" });
+                if rendering.insert(pointer) {
+                    for (i, element) in list.borrow().iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(", ");
+                        }
+                        stack::guard(|| element.render(out, inner, rendering));
+                    }
+                    rendering.remove(&pointer);
+                } else {
+                    out.push_str("...");
+                }
+                out.push_str(if style == Style::Debug { "])" } else { "]" });
+                Ok(())
+            },
+            (Value::Map(map), _) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                out.push_str(if style == Style::Debug { "Map({" } else { "{" });
+                if rendering.insert(pointer) {
+                    for (i, (key, value)) in map.borrow().iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(", ");
+                        }
+                        stack::guard(|| key.render(out, inner, rendering));
+                        out.push_str(": ");
+                        stack::guard(|| value.render(out, inner, rendering));
+                    }
+                    rendering.remove(&pointer);
+                } else {
+                    out.push_str("...");
+                }
+                out.push_str(if style == Style::Debug { "})" } else { "}" });
+                Ok(())
+            },
+            (Value::Range(start, end), Style::Debug) => write!(out, "Range({}..{})", start, end),
+            (Value::Range(start, end), _) => write!(out, "{}..{}", start, end),
+        }.expect("writing to a string cannot fail");
+    }
+
+    /// Moves the elements of a list or a map held by no other value to `pending`, so that they are dropped by the
+    /// loop of `drop` instead of recursively. Only the lists and maps are moved, other values having nothing nested.
+    fn take_nested(&mut self, pending: &mut Vec<Value>) {
+        let nested = |value: &Value| matches!(value, Value::List(_) | Value::Map(_));
+        match self {
+            Value::List(list) if Rc::strong_count(list) == 1 => {
+                pending.extend(list.borrow_mut().drain(..).filter(nested));
+            },
+            Value::Map(map) if Rc::strong_count(map) == 1 => {
+                pending.extend(map.borrow_mut().drain().flat_map(|(key, value)| [key, value]).filter(nested));
+            },
+            _ => (),
+        }
+    }
+}
@@ -1,57 +1,147 @@
 use std::collections::HashMap;
+use crate::ast::Slot;
 use crate::errors::CustomError;
 use crate::value::Value;
 use std::fmt::{Debug, Formatter};
 
+/// Variables of a running program in the slots given by the resolver, a slot being `None` until the variable is
+/// assigned: the global ones at the index of their name, the ones of blocks and function calls in their scope.
 pub struct VariableManager {
-    variables: Vec<HashMap<String, Value>>,
-    scope:usize,
+    globals: Vec<Option<Value>>,
+    /// Index of each global variable, kept for the functions and the host looking them up by name.
+    names: HashMap<String, usize>,
+    /// Functions provided by the host, found when no global variable has their name.
+    builtins: HashMap<String, Value>,
+    scopes: Vec<Vec<Option<Value>>>,
+    /// Number of function calls in progress.
+    calls: usize,
 }
 
+/// Deepest chain of function calls, beyond which a runaway recursion is reported instead of exhausting memory.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
 
 
 impl VariableManager {
     pub fn new() -> VariableManager {
-        VariableManager {variables: vec![HashMap::new()]
-        , scope:1}
+        VariableManager {globals: Vec::new(), names: HashMap::new(), builtins: HashMap::new(), scopes: Vec::new(), calls: 0}
+    }
+
+    fn get_slot(&self, depth: usize, index: usize) -> Option<&Value> {
+        match self.scopes[self.scopes.len() - 1 - depth].get(index) {
+            Some(value) => value.as_ref(),
+            None => None,
+        }
+    }
+
+    fn set_slot(&mut self, depth: usize, index: usize, value: Value) {
+        let depth = self.scopes.len() - 1 - depth;
+        let scope = &mut self.scopes[depth];
+        if index >= scope.len() {
+            scope.resize_with(index + 1, || None);
+        }
+        scope[index] = Some(value);
+    }
+
+    /// Index of the global variable `name`, if a top-level statement has been resolved with it.
+    pub(crate) fn find_global(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Index of the global variable `name`, given a new slot when it has none yet.
+    pub(crate) fn declare_global(&mut self, name: &str) -> usize {
+        if let Some(index) = self.find_global(name) {
+            return index;
+        }
+        self.globals.push(None);
+        self.names.insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    fn get_global(&self, name: &str) -> Option<&Value> {
+        self.globals[self.find_global(name)?].as_ref()
     }
 
+    /// Value of the global variable `name`, or of the builtin `name` when there is no such variable.
     pub fn get_variable(&self, name: &str) -> Result<Value, CustomError> {
-        for i in 0..self.scope{
-            match self.variables[i].get(name){
-                Some(value) => return Ok(value.clone()),
-                None => continue,
-            }
+        match self.get_global(name).or_else(|| self.builtins.get(name)){
+            Some(value) => return Ok(value.clone()),
+            None => return Err(CustomError::new_variable_not_found_error(name)),
         }
-        return Err(CustomError::new_variable_not_found_error(name))
-    }
-
-    pub fn set_variable(&mut self, name: &str, value: Value) {
-        for i in 0..self.scope{
-            match self.variables[i].get(name){
-                Some(_) => {
-                    self.variables[i].insert(name.to_string(), value);
-                    return;
-                },
-                None => continue,
-            }
+    }
+
+    /// Value of the variable `name` stored in `slot`, or of the global variable `name` while the slot is not set.
+    pub fn get_local_variable(&self, name: &str, slot: &Slot) -> Result<Value, CustomError> {
+        let value = match slot {
+            Slot::Global => None,
+            Slot::GlobalIndex(index) => self.globals[*index].as_ref(),
+            Slot::Local(depth, index) => self.get_slot(*depth, *index),
+        };
+        match value {
+            Some(value) => Ok(value.clone()),
+            None => self.get_variable(name),
         }
-        self.variables[self.scope-1].insert(name.to_string(), value);
+    }
+
+    /// Updates the variable `name` in `slot`, a slot not set yet standing for the global variable `name` when it exists.
+    pub fn set_variable(&mut self, name: &str, slot: &Slot, value: Value) {
+        let index = match slot {
+            Slot::Global => self.declare_global(name),
+            Slot::GlobalIndex(index) => *index,
+            Slot::Local(depth, index) => {
+                if self.get_slot(*depth, *index).is_none() {
+                    if let Some(index) = self.find_global(name).filter(|index| self.globals[*index].is_some()) {
+                        self.globals[index] = Some(value);
+                        return;
+                    }
+                }
+                self.set_slot(*depth, *index, value);
+                return;
+            },
+        };
+        self.globals[index] = Some(value);
+    }
+
+    /// Creates the variable `index` of the innermost scope, a parameter of the function being called or the variable of
+    /// a `for` loop.
+    pub fn declare_local(&mut self, index: usize, value: Value) {
+        self.set_slot(0, index, value);
+    }
+
+    /// Adds the builtin `name`, kept apart from the global variables so that it is not listed with them.
+    pub fn set_builtin(&mut self, name: &str, value: Value) {
+        self.builtins.insert(name.to_string(), value);
     }
 
     pub fn enter_scope(&mut self){
-        self.scope += 1;
-        self.variables.push(HashMap::new());
+        self.scopes.push(Vec::new());
     }
 
     pub fn exit_scope(&mut self){
-        self.variables.pop();
-        self.scope -= 1;
+        self.scopes.pop();
+    }
+
+    /// Opens the scope holding the parameters of a function call, unless too many calls are already in progress.
+    pub fn enter_function(&mut self) -> Result<(), CustomError>{
+        if self.calls == MAX_CALL_DEPTH {
+            return Err(CustomError::new_recursion_limit_error(MAX_CALL_DEPTH));
+        }
+        self.calls += 1;
+        self.enter_scope();
+        return Ok(());
+    }
+
+    pub fn exit_function(&mut self){
+        self.exit_scope();
+        self.calls -= 1;
     }
 }
 
 impl Debug for VariableManager{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.variables)
+        let globals: HashMap<&String, &Value> = self.names.iter()
+            .filter_map(|(name, index)| Some((name, self.globals[*index].as_ref()?)))
+            .collect();
+        write!(f, "[{:?}]", globals)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,257 @@
+use std::rc::Rc;
+use crate::compiler::{Chunk, OpCode};
+use crate::errors::CustomError;
+use crate::interpreter::Function;
+use crate::runtime::{get_boolean, is_true, locate};
+use crate::stack;
+use crate::value::{Value, ValueIterator};
+use crate::variables::VariableManager;
+
+/// The compiler balances the stack, an instruction always finds its operands.
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("operand missing from the stack")
+}
+
+/// Runs `chunk` in the current scope of `variables`, returning the value left on the stack or the returned value.
+pub fn run(chunk: &Chunk, variables: &mut VariableManager) -> Result<Value, CustomError> {
+    let mut scopes = 0;
+    let result = run_code(chunk, variables, &mut scopes);
+    // A `return` or an error leaves the scopes entered by the chunk open.
+    for _ in 0..scopes {
+        variables.exit_scope();
+    }
+    return result
+}
+
+/// Calls `function` with `arguments`, a function declared in Krab running its bytecode in a fresh frame.
+pub fn call(function: &Function, arguments: Vec<Value>, variables: &mut VariableManager) -> Result<Value, CustomError> {
+    match function.get_bytecode() {
+        Some((parameters, chunk)) => {
+            function.check_arity(parameters.len(), arguments.len())?;
+            variables.enter_function()?;
+            for (index, argument) in arguments.into_iter().enumerate() {
+                variables.declare_local(index, argument);
+            }
+            let result = stack::guard(|| run(chunk, variables));
+            variables.exit_function();
+            result
+        },
+        None => function.call(arguments, variables),
+    }
+}
+
+/// A `try` block in progress, with the state to unwind to before jumping to its handler.
+struct Handler {
+    target: usize,
+    stack: usize,
+    iterators: usize,
+    scopes: usize,
+    errors: usize,
+}
+
+/// State of a chunk being run.
+struct Frame<'a> {
+    chunk: &'a Chunk,
+    ip: usize,
+    stack: Vec<Value>,
+    /// Loops in progress, innermost last.
+    iterators: Vec<ValueIterator>,
+    /// `try` blocks in progress, innermost last.
+    handlers: Vec<Handler>,
+    /// Errors caught by a handler, waiting for `Catch`, or for `Rethrow` at the end of the `finally` block run for them.
+    errors: Vec<CustomError>,
+}
+
+fn run_code(chunk: &Chunk, variables: &mut VariableManager, scopes: &mut usize) -> Result<Value, CustomError> {
+    let mut frame = Frame{chunk, ip: 0, stack: Vec::new(), iterators: Vec::new(), handlers: Vec::new(), errors: Vec::new()};
+    while frame.ip < chunk.get_code().len() {
+        match frame.step(variables, scopes) {
+            Ok(Some(value)) => return Ok(value),
+            Ok(None) => (),
+            Err(error) => frame.catch(error, variables, scopes)?,
+        }
+    }
+    return Ok(frame.stack.pop().unwrap_or(Value::new_null()))
+}
+
+impl Frame<'_> {
+    /// Runs the next instruction, returning the value of a `return`.
+    fn step(&mut self, variables: &mut VariableManager, scopes: &mut usize) -> Result<Option<Value>, CustomError> {
+        let span = &self.chunk.get_span(self.ip);
+        let op = self.chunk.get_code()[self.ip];
+        self.ip += 1;
+        match op {
+            OpCode::Constant(i) => self.stack.push(self.chunk.get_constant(i).clone()),
+            OpCode::Load(i) => {
+                let (name, slot) = self.chunk.get_variable(i);
+                self.stack.push(variables.get_local_variable(name, slot).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Store(i) => {
+                let (name, slot) = self.chunk.get_variable(i);
+                variables.set_variable(name, slot, pop(&mut self.stack));
+            },
+            OpCode::Pop => {
+                pop(&mut self.stack);
+            },
+            OpCode::Binary(operator) => {
+                let right = pop(&mut self.stack);
+                let left = pop(&mut self.stack);
+                self.stack.push(left.binary(operator, &right).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Unary(operator) => {
+                let value = pop(&mut self.stack);
+                self.stack.push(value.unary(operator).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Range => {
+                let end = pop(&mut self.stack);
+                let start = pop(&mut self.stack);
+                self.stack.push(Value::new_range(&start, &end).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Compare(operator) => {
+                let right = pop(&mut self.stack);
+                let left = pop(&mut self.stack);
+                self.stack.push(Value::new_boolean(left.compare(operator, &right).map_err(|e| locate(e, span))?));
+            },
+            OpCode::ShortCircuit(operator, target) => {
+                let left = get_boolean(pop(&mut self.stack), span)?;
+                match operator {
+                    '&' => {
+                        if !left {
+                            self.stack.push(Value::new_boolean(false));
+                            self.ip = target;
+                        }
+                    },
+                    '|' => {
+                        if left {
+                            self.stack.push(Value::new_boolean(true));
+                            self.ip = target;
+                        }
+                    },
+                    _ => return Err(locate(CustomError::new_operator_not_found_error(operator), span)),
+                }
+            },
+            OpCode::CheckBoolean => {
+                let right = get_boolean(pop(&mut self.stack), span)?;
+                self.stack.push(Value::new_boolean(right));
+            },
+            OpCode::CheckFunction => {
+                match self.stack.last() {
+                    Some(Value::Function(_)) => (),
+                    _ => return Err(locate(CustomError::new_operation_error(&format!("Cannot call {:?}", pop(&mut self.stack))), span)),
+                }
+            },
+            OpCode::Jump(target) => self.ip = target,
+            OpCode::JumpIfFalse(target) => {
+                if !is_true(pop(&mut self.stack)) {
+                    self.ip = target;
+                }
+            },
+            OpCode::EnterScope => {
+                variables.enter_scope();
+                *scopes += 1;
+            },
+            OpCode::ExitScope => {
+                variables.exit_scope();
+                *scopes -= 1;
+            },
+            OpCode::Iterate(count) => {
+                let iterable = pop(&mut self.stack);
+                self.iterators.push(iterable.iterate(count).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Next(count, target) => {
+                match self.iterators.last_mut().expect("loop without an iterator").next() {
+                    Some((element, value)) => {
+                        self.stack.push(element);
+                        if let (Some(value), 2) = (value, count) {
+                            self.stack.push(value);
+                        }
+                    },
+                    None => self.ip = target,
+                }
+            },
+            OpCode::DropIterator => {
+                self.iterators.pop();
+            },
+            OpCode::Declare(i) => {
+                let value = pop(&mut self.stack);
+                variables.declare_local(i, value);
+            },
+            OpCode::Function(i, j) => {
+                let definition = self.chunk.get_function(i);
+                let (name, slot) = self.chunk.get_variable(j);
+                variables.set_variable(name, slot, Value::Function(Rc::new(Function::new_defined(Rc::clone(definition)))));
+            },
+            OpCode::Call(count) => {
+                let arguments = self.stack.split_off(self.stack.len() - count);
+                let function = match &pop(&mut self.stack) {
+                    Value::Function(function) => Rc::clone(function),
+                    value => return Err(locate(CustomError::new_operation_error(&format!("Cannot call {:?}", value)), span)),
+                };
+                self.stack.push(call(&function, arguments, variables).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Return => return Ok(Some(pop(&mut self.stack))),
+            OpCode::List(count) => {
+                let elements = self.stack.split_off(self.stack.len() - count);
+                self.stack.push(Value::new_list(elements));
+            },
+            OpCode::Map(count) => {
+                let mut values = self.stack.split_off(self.stack.len() - 2 * count).into_iter();
+                let mut entries = Vec::with_capacity(count);
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    entries.push((key, value));
+                }
+                self.stack.push(Value::new_map(entries).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Index => {
+                let index = pop(&mut self.stack);
+                let target = pop(&mut self.stack);
+                self.stack.push(target.get_index(&index).map_err(|e| locate(e, span))?);
+            },
+            OpCode::Slice => {
+                let end = pop(&mut self.stack);
+                let start = pop(&mut self.stack);
+                let target = pop(&mut self.stack);
+                self.stack.push(target.slice(&start, &end).map_err(|e| locate(e, span))?);
+            },
+            OpCode::SetIndex => {
+                let value = pop(&mut self.stack);
+                let index = pop(&mut self.stack);
+                let target = pop(&mut self.stack);
+                target.set_index(&index, value).map_err(|e| locate(e, span))?;
+            },
+            OpCode::Try(target) => {
+                let handler = Handler{target, stack: self.stack.len(), iterators: self.iterators.len(), scopes: *scopes, errors: self.errors.len()};
+                self.handlers.push(handler);
+            },
+            OpCode::EndTry => {
+                self.handlers.pop();
+            },
+            OpCode::Catch => {
+                let error = self.errors.pop().expect("no error waiting to be caught");
+                self.stack.push(error.into_value());
+            },
+            OpCode::Rethrow => return Err(self.errors.pop().expect("no error waiting to be raised again")),
+            OpCode::Throw => return Err(locate(CustomError::new_thrown_error(pop(&mut self.stack)), span)),
+        }
+        return Ok(None)
+    }
+
+    /// Unwinds to the innermost `try` block and jumps to its handler with `error` waiting to be caught, or fails with
+    /// `error` outside of any `try` block.
+    fn catch(&mut self, error: CustomError, variables: &mut VariableManager, scopes: &mut usize) -> Result<(), CustomError> {
+        let handler = match self.handlers.pop() {
+            Some(handler) => handler,
+            None => return Err(error),
+        };
+        self.stack.truncate(handler.stack);
+        self.iterators.truncate(handler.iterators);
+        while *scopes > handler.scopes {
+            variables.exit_scope();
+            *scopes -= 1;
+        }
+        self.errors.truncate(handler.errors);
+        self.errors.push(error);
+        self.ip = handler.target;
+        return Ok(())
+    }
+}
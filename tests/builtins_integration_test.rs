@@ -0,0 +1,207 @@
+extern crate krab_language;
+use std::env;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use krab_language::errors::CustomError;
+use krab_language::interpreter::{Engine, Interpreter};
+use krab_language::lexer::Lexer;
+use krab_language::parser::Parser;
+use krab_language::value::Value;
+
+/// Writer keeping what the scripts print so that the tests can read it back.
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Interpreter on the engine named by `KRAB_ENGINE`, so that the tests can be run against either engine.
+fn get_interpreter() -> Interpreter {
+    let engine = env::var("KRAB_ENGINE").ok().and_then(|name| Engine::from_name(&name));
+    Interpreter::with_engine(engine.unwrap_or(Engine::TreeWalker))
+}
+
+fn eq_values(value1: &Value, value2: &Value) -> bool {
+    value1.eq(value2).unwrap_or_default()
+}
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<Value, CustomError> {
+    let tokens = Lexer::new().lex(source)?;
+    let instructions = Parser::new().parse_instructions(tokens)?;
+    interpreter.execute_instructions(&instructions)
+}
+
+fn run_with_output(source: &str) -> String {
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = get_interpreter();
+    interpreter.set_output(SharedBuffer(Rc::clone(&buffer)));
+    match run(&mut interpreter, source) {
+        Ok(_) => {},
+        Err(_) => assert!(false)
+    }
+    let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+    output
+}
+
+fn evaluate(source: &str) -> Result<Value, CustomError> {
+    let mut interpreter = get_interpreter();
+    run(&mut interpreter, &format!("result = {};", source))?;
+    interpreter._get_variable("result")
+}
+
+#[test]
+fn test_print_and_println(){
+    let output = run_with_output("print(\"a\", 1); print(2.5); println(); println(true, null, \"end\");");
+    assert_eq!(output, "a 12.5\ntrue null end\n");
+}
+
+#[test]
+fn test_len(){
+    match evaluate("len(\"krab🦀\")") {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(5))),
+        Err(_) => assert!(false)
+    }
+    match evaluate("len(12)") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot get the length of Integer(12)")))
+    }
+}
+
+#[test]
+fn test_type_of(){
+    let output = run_with_output("fn f() {}; println(type_of(1), type_of(1.5), type_of(\"s\"), type_of(false), type_of(null), type_of(f), type_of(println));");
+    assert_eq!(output, "integer float string boolean null function function\n");
+}
+
+#[test]
+fn test_str(){
+    let output = run_with_output("println(str(1) + str(2.0) + str(true) + str(null));");
+    assert_eq!(output, "12.0truenull\n");
+}
+
+#[test]
+fn test_int(){
+    let cases = vec![
+        ("int(7)", Value::new_integer(7)),
+        ("int(-7.9)", Value::new_integer(-7)),
+        ("int(\" 42 \")", Value::new_integer(42)),
+        ("int(true)", Value::new_integer(1)),
+    ];
+    for (source, expected) in cases {
+        match evaluate(source) {
+            Ok(value) => assert!(eq_values(&value, &expected)),
+            Err(_) => assert!(false)
+        }
+    }
+    match evaluate("int(\"4x\")") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_conversion_error("String(4x)", "integer")))
+    }
+    match evaluate("int(float(\"1e19\"))") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_conversion_error("Float(10000000000000000000)", "integer")))
+    }
+    match evaluate("int(null)") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_conversion_error("Null", "integer")))
+    }
+}
+
+#[test]
+fn test_float(){
+    let cases = vec![
+        ("float(2)", Value::new_float(2.0)),
+        ("float(\"2.5\")", Value::new_float(2.5)),
+        ("float(false)", Value::new_float(0.0)),
+    ];
+    for (source, expected) in cases {
+        match evaluate(source) {
+            Ok(value) => assert!(eq_values(&value, &expected)),
+            Err(_) => assert!(false)
+        }
+    }
+    match evaluate("float(\"krab\")") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_conversion_error("String(krab)", "float")))
+    }
+}
+
+#[test]
+fn test_bool(){
+    let output = run_with_output("println(bool(0), bool(3), bool(0.0), bool(\"\"), bool(\"a\"), bool(null), bool(true));");
+    assert_eq!(output, "false true false false true false true\n");
+}
+
+#[test]
+fn test_list_builtins(){
+    let expected = [
+        ("len([1, [2, 3], \"abc\"])", Value::new_integer(3)),
+        ("len([])", Value::new_integer(0)),
+        ("type_of([])", Value::new_string("list")),
+        ("bool([])", Value::new_boolean(false)),
+        ("bool([0])", Value::new_boolean(true)),
+        ("str([1, \"a\", [true, null]])", Value::new_string("[1, \"a\", [true, null]]")),
+    ];
+    for (source, value) in expected {
+        match evaluate(source) {
+            Ok(result) => assert!(eq_values(&result, &value), "{}", source),
+            Err(_) => assert!(false)
+        }
+    }
+    assert_eq!(run_with_output("println([1.0, \"b\"], []);"), "[1.0, \"b\"] []\n");
+}
+
+#[test]
+fn test_map_builtins(){
+    let expected = [
+        ("keys({\"b\": 1, \"a\": 2, 3: 4})", "[\"b\", \"a\", 3]"),
+        ("values({\"b\": 1, \"a\": 2, 3: 4})", "[1, 2, 4]"),
+        ("has({\"a\": 1}, \"a\")", "true"),
+        ("has({1: 1}, 1.0)", "true"),
+        ("has({\"a\": 1}, 1)", "false"),
+        ("len({\"a\": 1, \"b\": 2})", "2"),
+        ("type_of({})", "map"),
+        ("bool({})", "false"),
+        ("str({\"a\": [1, {}]})", "{\"a\": [1, {}]}"),
+    ];
+    for (source, value) in expected {
+        match evaluate(source) {
+            Ok(result) => assert_eq!(result._to_string(), value, "{}", source),
+            Err(_) => assert!(false)
+        }
+    }
+    match evaluate("keys([1])") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot get the keys of List([Integer(1)])")))
+    }
+    match evaluate("has(1, 1)") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot look up a key in Integer(1)")))
+    }
+}
+
+#[test]
+fn test_builtins_apart_from_variables(){
+    let mut interpreter = get_interpreter();
+    match run(&mut interpreter, "a = len([1, 2]);") {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    assert_eq!(format!("{:?}", interpreter.variables), "[{\"a\": Integer(2)}]");
+    // A global variable hides the builtin of the same name.
+    match run(&mut interpreter, "len = 3; b = len;") {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("b") {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(3))),
+        Err(_) => assert!(false)
+    }
+}
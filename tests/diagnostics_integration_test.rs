@@ -0,0 +1,110 @@
+extern crate krab_language;
+use std::env;
+use krab_language::lexer::{Lexer, Token};
+use krab_language::parser::Parser;
+use krab_language::interpreter::{Engine, Interpreter};
+use krab_language::errors::CustomError;
+use krab_language::value::Value;
+use krab_language::diagnostics::Renderer;
+use krab_language::span::Span;
+
+/// Interpreter on the engine named by `KRAB_ENGINE`, so that the tests can be run against either engine.
+fn get_interpreter() -> Interpreter {
+    let engine = env::var("KRAB_ENGINE").ok().and_then(|name| Engine::from_name(&name));
+    Interpreter::with_engine(engine.unwrap_or(Engine::TreeWalker))
+}
+
+fn get_error(source: &str) -> CustomError {
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    let tokens = match lexer.lex(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return e,
+    };
+    let instructions = match parser.parse_instructions(tokens) {
+        Ok(instructions) => instructions,
+        Err(e) => return e,
+    };
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => panic!("{} should fail", source),
+        Err(e) => e,
+    }
+}
+
+fn render(source: &str) -> String {
+    Renderer::new(false).render(&get_error(source), source, "main.krab")
+}
+
+#[test]
+fn test_render_lexer_error(){
+    let expected = "error[E0001]: LexerError: Unknown character: $\n --> main.krab:1:7\n  |\n1 | a = 1 $ 2;\n  |       ^\n";
+    assert_eq!(render("a = 1 $ 2;"), expected);
+}
+
+#[test]
+fn test_render_parser_error_with_label(){
+    let source = "a = 0;\nwhile a < 3 {\n  a = a + 1;\n";
+    let expected = "error[E0002]: ParserError: '}' expected but none found\n --> main.krab:2:13\n  |\n2 | while a < 3 {\n  | ----- loop started here\n  |             ^\n";
+    assert_eq!(render(source), expected);
+}
+
+#[test]
+fn test_render_parser_error_with_note(){
+    let expected = "error[E0002]: ParserError: 'return' outside of a function\n --> main.krab:1:1\n  |\n1 | return 1;\n  | ^^^^^^\n  |\n  = note: 'return' can only be used inside the body of a 'fn'\n";
+    assert_eq!(render("return 1;"), expected);
+}
+
+#[test]
+fn test_render_runtime_error(){
+    let source = "a = 1;\nb = a + \"x\";";
+    let expected = "error[E0005]: OperationError: Cannot add Integer(1) and String(x)\n --> main.krab:2:5\n  |\n2 | b = a + \"x\";\n  |     ^^^^^^^\n";
+    assert_eq!(render(source), expected);
+}
+
+#[test]
+fn test_render_error_without_span(){
+    let error = CustomError::new_file_not_found_error("missing.krab");
+    assert_eq!(Renderer::new(false).render(&error, "", "missing.krab"), "error[E0009]: FileNotFoundError: File missing.krab not found\n");
+}
+
+#[test]
+fn test_render_error_without_location(){
+    // Tokens built by hand have no location, so the errors pointing at them do not either.
+    let tokens = vec![Token::new_identifier("x"), Token::new_assign("="), Token::new_operator("+")];
+    let error = match Parser::new().parse_instructions(tokens) {
+        Ok(_) => panic!("x = + should fail"),
+        Err(e) => e,
+    };
+    assert!(error.get_span().is_none());
+    assert_eq!(Renderer::new(false).render(&error, "", "main.krab"), format!("error[E0002]: ParserError: {}\n", error.get_message()));
+    let error = CustomError::new_parser_error("Unexpected token").with_span(Span::default()).with_label(Span::default(), "here");
+    assert_eq!(Renderer::new(false).render(&error, "a = 1;", "main.krab"), "error[E0002]: ParserError: Unexpected token\n");
+}
+
+#[test]
+fn test_render_colored(){
+    let source = "a = b;";
+    let rendered = Renderer::new(true).render(&get_error(source), source, "main.krab");
+    assert!(rendered.starts_with("\x1b[1;31merror[E0003]\x1b[0m"));
+    assert!(rendered.contains("\x1b[1;31m    ^\x1b[0m"));
+}
+
+#[test]
+fn test_error_codes(){
+    assert_eq!(CustomError::new_lexer_error("").get_code(), "E0001");
+    assert_eq!(CustomError::new_parser_error("").get_code(), "E0002");
+    assert_eq!(CustomError::new_variable_not_found_error("a").get_code(), "E0003");
+    assert_eq!(CustomError::new_operator_not_found_error('%').get_code(), "E0004");
+    assert_eq!(CustomError::new_operation_error("").get_code(), "E0005");
+    assert_eq!(CustomError::new_type_error("").get_code(), "E0006");
+    assert_eq!(CustomError::new_conversion_error("a", "integer").get_code(), "E0007");
+    assert_eq!(CustomError::new_io_error("").get_code(), "E0008");
+    assert_eq!(CustomError::new_file_not_found_error("a").get_code(), "E0009");
+    assert_eq!(CustomError::new_division_by_zero_error().get_code(), "E0010");
+    assert_eq!(CustomError::new_integer_overflow_error().get_code(), "E0011");
+    assert_eq!(CustomError::new_recursion_limit_error(1).get_code(), "E0012");
+    assert_eq!(CustomError::new_index_error(1, 0).get_code(), "E0013");
+    assert_eq!(CustomError::new_key_error("a").get_code(), "E0014");
+    assert_eq!(CustomError::new_thrown_error(Value::new_null()).get_code(), "E0015");
+}
@@ -0,0 +1,277 @@
+extern crate krab_language;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use krab_language::errors::CustomError;
+use krab_language::interpreter::{Engine, Interpreter};
+use krab_language::lexer::Lexer;
+use krab_language::parser::Parser;
+use krab_language::value::Value;
+
+/// Writer keeping what the scripts print so that the tests can read it back.
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<Value, CustomError> {
+    let tokens = Lexer::new().lex(source)?;
+    let instructions = Parser::new().parse_instructions(tokens)?;
+    interpreter.execute_instructions(&instructions)
+}
+
+fn describe_error(error: &CustomError) -> String {
+    let span = match error.get_span() {
+        Some(span) => span.to_string(),
+        None => "no span".to_string(),
+    };
+    format!("{} at {}", error, span)
+}
+
+/// Runs `source` with `engine` and describes everything observable: the output, the outcome and the `variables` afterwards.
+fn observe(engine: Engine, source: &str, variables: &[&str]) -> String {
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::with_engine(engine);
+    interpreter.set_output(SharedBuffer(Rc::clone(&buffer)));
+    let outcome = match run(&mut interpreter, source) {
+        Ok(value) => format!("{:?}", value),
+        Err(e) => describe_error(&e),
+    };
+    let mut observed = format!("output: {:?}\noutcome: {}\n", String::from_utf8(buffer.borrow().clone()).unwrap(), outcome);
+    for name in variables {
+        match interpreter._get_variable(name) {
+            Ok(value) => observed.push_str(&format!("{} = {:?}\n", name, value)),
+            Err(e) => observed.push_str(&format!("{}: {}\n", name, describe_error(&e))),
+        }
+    }
+    observed
+}
+
+/// Checks that both engines observe the same thing, and returns it.
+fn cross_check(source: &str, variables: &[&str]) -> String {
+    let expected = observe(Engine::TreeWalker, source, variables);
+    assert_eq!(observe(Engine::Bytecode, source, variables), expected, "engines disagree on {}", source);
+    expected
+}
+
+#[test]
+fn test_engine_selection(){
+    assert_eq!(Interpreter::new().get_engine(), Engine::TreeWalker);
+    assert_eq!(Engine::from_name("tree"), Some(Engine::TreeWalker));
+    assert_eq!(Engine::from_name("bytecode"), Some(Engine::Bytecode));
+    assert_eq!(Engine::from_name("jit"), None);
+    let mut interpreter = Interpreter::with_engine(Engine::Bytecode);
+    assert_eq!(interpreter.get_engine(), Engine::Bytecode);
+    match run(&mut interpreter, "a = 20;") {
+        Ok(_) => (),
+        Err(_) => assert!(false),
+    }
+    interpreter.set_engine(Engine::TreeWalker);
+    match run(&mut interpreter, "a = a + 1;") {
+        Ok(_) => (),
+        Err(_) => assert!(false),
+    }
+    match interpreter._get_variable("a") {
+        Ok(value) => assert!(value.eq(&Value::new_integer(21)).unwrap()),
+        Err(_) => assert!(false),
+    }
+}
+
+#[test]
+fn test_cross_check_expressions(){
+    let observed = cross_check("a = 1 + 2 * 3 - 4 / 2; b = -a; c = \"ab\" * 3; d = 1.5 + 2.5; e = !(a > 2) or a == 5; f = a >= 5 and b <= 0;", &["a", "b", "c", "d", "e", "f"]);
+    assert!(observed.contains("a = Integer(5)"));
+    assert!(observed.contains("e = Boolean(true)"));
+}
+
+#[test]
+fn test_cross_check_control_flow(){
+    let source = "i = 0; s = 0;
+while i < 10 {
+    i = i + 1;
+    if i == 3 { s = s + 100; } else if i > 7 { s = s - 1; } else { s = s + i; };
+    tmp = i;
+};
+if 1 { never = true; };";
+    let observed = cross_check(source, &["i", "s", "tmp", "never"]);
+    assert!(observed.contains("s = Integer(122)"));
+}
+
+#[test]
+fn test_cross_check_functions(){
+    let source = "
+g = 1;
+fn fact(n) { if n <= 1 { return 1; }; return n * fact(n - 1); };
+fn first_above(limit) { i = 0; while true { i = i + 1; if i * i > limit { return i; }; }; };
+fn nothing() { x = 1; };
+fn set_global() { g = g + 1; };
+a = fact(10);
+b = first_above(50);
+c = nothing();
+set_global();
+alias = fact;
+d = alias(3);
+println(a, b, c, g, d);";
+    let observed = cross_check(source, &["a", "b", "c", "g", "d", "x", "n", "i"]);
+    assert!(observed.contains("output: \"3628800 8 null 2 6\\n\""));
+}
+
+#[test]
+fn test_cross_check_lists(){
+    let source = "
+xs = [1, 2, 3];
+fn push_twice(list, value) { return list + [value, value]; };
+fn fill(list, value) { i = 0; while i < len(list) { list[i] = value; i = i + 1; }; };
+ys = push_twice(xs, 4)[-3:];
+fill(xs, 0);
+zs = [xs, ys][1][0:2];
+println(xs, ys, zs, xs == [0, 0, 0.0], xs[-4]);";
+    let observed = cross_check(source, &["xs", "ys", "zs"]);
+    assert!(observed.contains("output: \"\""));
+    assert!(observed.contains("IndexError:Index -4 out of range for a list of length 3 at 8:40"));
+    assert!(observed.contains("zs = List([Integer(3), Integer(4)])"));
+}
+
+#[test]
+fn test_cross_check_maps(){
+    let source = "
+counts = {};
+words = [\"b\", \"a\", \"b\", 1, 1.0];
+i = 0;
+while i < len(words) {
+    word = words[i];
+    if (has(counts, word)) { counts[word] = counts[word] + 1; } else { counts[word] = 1; };
+    i = i + 1;
+};
+println(counts, keys(counts), values(counts), counts == {1: 2, \"a\": 1, \"b\": 2});
+missing = counts[\"c\"];";
+    let observed = cross_check(source, &["counts", "missing"]);
+    assert!(observed.contains("output: \"{\\\"b\\\": 2, \\\"a\\\": 1, 1: 2} [\\\"b\\\", \\\"a\\\", 1] [2, 1, 2] true\\n\""));
+    assert!(observed.contains("KeyError:Key \"c\" not found at 11:11"));
+}
+
+#[test]
+fn test_cross_check_for_loops(){
+    let source = "
+fn sum_until(list, limit) { total = 0; for x in list { total = total + x; if total > limit { return total; }; }; return -1; };
+a = sum_until(0..1_000_000, 100);
+b = sum_until([1, 2], 100);
+grid = [];
+for i in 0..3 { row = []; for j in 0..i { row = row + [j]; }; grid = grid + [row]; };
+for k, v in ({\"k\": [1], 2: \"v\"}) { print(k, v, \"\"); };
+for c in \"ab\" { print(c); };
+println(a, b, grid);
+for x in [1, 0] { y = 1 / x; };";
+    let observed = cross_check(source, &["a", "b", "grid", "x", "y", "row"]);
+    assert!(observed.contains("output: \"k [1] 2 v ab105 -1 [[], [0], [0, 1]]\\n\""));
+    assert!(observed.contains("DivisionByZeroError:Division by zero at 10:23"));
+}
+
+#[test]
+fn test_cross_check_break_and_continue(){
+    let source = "
+fn find(grid, target) { at = []; for row in grid { for x in row { if x == target { at = [row, x]; break; }; }; if len(at) > 0 { break; }; }; return at; };
+a = find([[1, 2], [3, 4]], 3);
+skipped = [];
+i = 0;
+while i < 6 { i = i + 1; if true { if i % 3 != 0 { continue; }; }; skipped = skipped + [i]; };
+for k, v in ({\"a\": 1, \"b\": 2}) { if v == 2 { break; }; print(k); };
+println(a, skipped);
+for x in [1, 0] { if x == 2 { break; }; y = 1 / x; };";
+    let observed = cross_check(source, &["a", "skipped", "i", "at", "row", "y"]);
+    assert!(observed.contains("output: \"a[[3, 4], 3] [3, 6]\\n\""), "{}", observed);
+    assert!(observed.contains("DivisionByZeroError:Division by zero at 9:45"), "{}", observed);
+}
+
+#[test]
+fn test_cross_check_exceptions(){
+    let source = "
+fn safe_div(a, b) { try { return a / b; } catch e { return e[\"kind\"]; } finally { print(\"div \"); }; };
+a = [safe_div(6, 3), safe_div(1, 0)];
+fn first_error(list) { for x in list { try { if x < 0 { throw [\"negative\", x]; }; } catch e { return e; }; }; };
+b = first_error([1, -2, -3]);
+log = [];
+for i in 0..4 { try { if i == 1 { continue; }; if i == 2 { break; }; } finally { log = log + [i]; }; };
+fn rethrow() { try { try { missing; } finally { print(\"cleanup \"); }; } catch e { throw e[\"message\"]; }; };
+c = null;
+try { rethrow(); } catch e { c = e; };
+println(a, b, log);
+d = null;
+try { for x in [1] { for y in [2] { throw y; }; }; } catch e { d = e; };
+throw {\"code\": d};";
+    let observed = cross_check(source, &["a", "b", "c", "d", "log", "e", "x"]);
+    assert!(observed.contains("output: \"div div cleanup [2, \\\"DivisionByZeroError\\\"] [\\\"negative\\\", -2] [0, 1, 2]\\n\""), "{}", observed);
+    assert!(observed.contains("c = String(Variable missing does not exist)"), "{}", observed);
+    assert!(observed.contains("ThrownError:{\"code\": 2} at 14:1"), "{}", observed);
+}
+
+#[test]
+fn test_cross_check_short_circuit(){
+    let observed = cross_check("fn boom() { return missing; }; a = false and boom(); b = true or boom(); c = true and boom();", &["a", "b", "c"]);
+    assert!(observed.contains("VariableNotFoundError:Variable missing does not exist"));
+}
+
+#[test]
+fn test_cross_check_errors(){
+    let sources = [
+        "a = 1 / 0;",
+        "a = 1 + \"a\";",
+        "a = -\"a\";",
+        "a = 1 and true;",
+        "a = false or 1;",
+        "a = true and 1;",
+        "a = undefined + 1;",
+        "a = 1; a(missing);",
+        "fn f(x) { return x; }; f(1, 2);",
+        "len(1, 2);",
+        "int(\"abc\");",
+        "fn f() { { { return 1 / 0; }; }; }; a = f();",
+        "a = 5 % 0;",
+        "a = 9223372036854775807 + 1;",
+        "a = \"ab\" * -1;",
+        "fn f(n) { return f(n + 1); }; a = f(0);",
+        "a = [1][1];",
+        "a = [1]; a[-2] = 0;",
+        "a = [1][\"0\"];",
+        "a = 1; a[0] = 1;",
+        "a = [1][true:];",
+        "a = {}[\"k\"];",
+        "a = {1: 2}[0:1];",
+        "a = keys([]);",
+        "for x in 1 { };",
+        "for k, v in \"ab\" { };",
+        "for x in 1..\"2\" { };",
+        "fn f() { for i in 0..10 { for j in 0..10 { if j == 5 { return 1 / 0; }; }; }; }; a = f();",
+        "throw \"a\";",
+        "try { a = 1 / 0; } finally { a = 1; };",
+        "try { a = 1; } catch e { } finally { a = 1 / 0; };",
+        "try { throw 1; } catch e { a = e / 0; };",
+    ];
+    for source in sources {
+        let observed = cross_check(source, &["a"]);
+        assert!(observed.contains("Error"), "{} should fail", source);
+    }
+}
+
+#[test]
+fn test_cross_check_scopes_after_error(){
+    // An error raised inside nested blocks and calls must not leave their scopes open.
+    let source = "fn f(n) { while true { if n > 2 { return undefined; }; n = n + 1; local = n; }; }; f(0);";
+    let mut interpreter = Interpreter::with_engine(Engine::Bytecode);
+    match run(&mut interpreter, source) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("undefined"))),
+    }
+    // Back in the global scope, `top` is visible from a function.
+    match run(&mut interpreter, "top = 1; fn get_top() { return top; }; r = get_top();") {
+        Ok(_) => (),
+        Err(_) => assert!(false),
+    }
+}
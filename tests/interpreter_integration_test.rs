@@ -1,17 +1,17 @@
 extern crate krab_language;
-use krab_language::interpreter::{StringValue, Interpreter, FloatValue, Variable, Affectation, Operation, IntegerValue, Condition, ConditionBlock, ConditionLoop, InstructionBlock};
+use std::env;
+use krab_language::interpreter::{Engine, Valuable, StringValue, Interpreter, FloatValue, Variable, Affectation, Operation, IntegerValue, Condition, ConditionBlock, ConditionLoop, InstructionBlock, UnaryOperation, BooleanValue, NullValue, LogicalOperation, Function, FunctionDeclaration, FunctionCall, Return, ListValue, MapValue, Index, IndexAffectation, Instruction, ForLoop, RangeValue, Break, Continue, Throw, TryBlock};
 use krab_language::value::Value;
-use krab_language::errors::CustomError;
+use krab_language::errors::{CustomError, ErrorKind};
 
+/// Interpreter on the engine named by `KRAB_ENGINE`, so that the tests can be run against either engine.
 fn get_interpreter() -> Interpreter {
-    Interpreter::new()
+    let engine = env::var("KRAB_ENGINE").ok().and_then(|name| Engine::from_name(&name));
+    Interpreter::with_engine(engine.unwrap_or(Engine::TreeWalker))
 }
 
 fn eq_values(value1: &Value, value2: &Value) -> bool {
-    match value1.eq(value2) {
-        Ok(b) => b,
-        Err(_) => false
-    }
+    value1.eq(value2).unwrap_or_default()
 }
 
 #[test]
@@ -297,10 +297,7 @@ fn test_condition_loop(){
         Ok(_) => {},
         Err(_) => assert!(false)
     }
-    match {
-        let this = &mut interpreter;
-        this.variables.get_variable("a")
-    }{
+    match interpreter.variables.get_variable("a") {
         Ok(v) => assert!(eq_values(&v,&Value::new_integer(100) )),
         Err(_) => assert!(false)
     }
@@ -320,10 +317,7 @@ fn test_access_variable_in_condition_loop(){
         Ok(_) => {},
         Err(_) => assert!(false)
     }
-    match {
-        let this = &mut interpreter;
-        this.variables.get_variable("b")
-    }{
+    match interpreter.variables.get_variable("b") {
         Ok(_) => assert!(false),
         Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("b")))
     }
@@ -359,4 +353,791 @@ fn test_condition_block_with_else_fail(){
         Ok(v) => assert!(eq_values(&v,&Value::new_integer(2))),
         Err(_) => assert!(false)
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_condition_greater_or_equal(){
+    let mut interpreter = get_interpreter();
+    let condition = Condition::new(Box::new(IntegerValue::new(10)), Box::new(IntegerValue::new(10)), '≥');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let condition = Condition::new(Box::new(FloatValue::new(9.5)), Box::new(FloatValue::new(10.0)), '≥');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(false))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_condition_less_or_equal(){
+    let mut interpreter = get_interpreter();
+    let condition = Condition::new(Box::new(IntegerValue::new(10)), Box::new(IntegerValue::new(10)), '≤');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let condition = Condition::new(Box::new(FloatValue::new(10.5)), Box::new(FloatValue::new(10.0)), '≤');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(false))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_condition_equality(){
+    let mut interpreter = get_interpreter();
+    let condition = Condition::new(Box::new(StringValue::new("krab")), Box::new(StringValue::new("krab")), '=');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let condition = Condition::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(2)), '≠');
+    match interpreter.execute(&condition) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_unary_negation(){
+    let mut interpreter = get_interpreter();
+    let negation = UnaryOperation::new(Box::new(IntegerValue::new(5)), '-');
+    match interpreter.execute(&negation) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(-5))),
+        Err(_) => assert!(false)
+    }
+    let negation = UnaryOperation::new(Box::new(FloatValue::new(2.5)), '-');
+    match interpreter.execute(&negation) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_float(-2.5))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_unary_negation_error(){
+    let mut interpreter = get_interpreter();
+    let negation = UnaryOperation::new(Box::new(StringValue::new("krab")), '-');
+    match interpreter.execute(&negation) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot negate String(krab)")))
+    }
+}
+
+#[test]
+fn test_unary_not(){
+    let mut interpreter = get_interpreter();
+    let condition = Condition::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(2)), '<');
+    let not = UnaryOperation::new(Box::new(condition), '!');
+    match interpreter.execute(&not) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(false))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_boolean_and_null_literals(){
+    let mut interpreter = get_interpreter();
+    let affectation = Affectation::new("flag", Box::new(BooleanValue::new(true)));
+    let _ = interpreter.execute(&affectation);
+    match interpreter._get_variable("flag"){
+        Ok(v) => assert!(eq_values(&v, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let affectation = Affectation::new("sentinel", Box::new(NullValue::new()));
+    let _ = interpreter.execute(&affectation);
+    match interpreter._get_variable("sentinel"){
+        Ok(Value::Null()) => {},
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_logical_and_short_circuit(){
+    let mut interpreter = get_interpreter();
+    let and = LogicalOperation::new(Box::new(BooleanValue::new(false)), Box::new(Variable::new("missing")), '&');
+    match interpreter.execute(&and) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(false))),
+        Err(_) => assert!(false)
+    }
+    let and = LogicalOperation::new(Box::new(BooleanValue::new(true)), Box::new(Variable::new("missing")), '&');
+    match interpreter.execute(&and) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("missing")))
+    }
+}
+
+#[test]
+fn test_logical_or_short_circuit(){
+    let mut interpreter = get_interpreter();
+    let or = LogicalOperation::new(Box::new(BooleanValue::new(true)), Box::new(Variable::new("missing")), '|');
+    match interpreter.execute(&or) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let or = LogicalOperation::new(Box::new(BooleanValue::new(false)), Box::new(BooleanValue::new(false)), '|');
+    match interpreter.execute(&or) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_boolean(false))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_logical_operation_on_non_boolean(){
+    let mut interpreter = get_interpreter();
+    let and = LogicalOperation::new(Box::new(IntegerValue::new(1)), Box::new(BooleanValue::new(true)), '&');
+    match interpreter.execute(&and) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Expected a boolean in logical operation but found Integer(1)")))
+    }
+}
+
+#[test]
+fn test_condition_block_with_else_if(){
+    let mut interpreter = get_interpreter();
+    let affectation = Affectation::new("a", Box::new(IntegerValue::new(0)));
+    let _ = interpreter.execute(&affectation);
+    let condition1 = Condition::new(Box::new(IntegerValue::new(10)), Box::new(IntegerValue::new(100)), '>');
+    let condition2 = Condition::new(Box::new(IntegerValue::new(10)), Box::new(IntegerValue::new(100)), '<');
+    let affectation1 = Affectation::new("a", Box::new(IntegerValue::new(1)));
+    let affectation2 = Affectation::new("a", Box::new(IntegerValue::new(2)));
+    let affectation3 = Affectation::new("a", Box::new(IntegerValue::new(3)));
+    let else_if = ConditionBlock::new_with_else(Box::new(condition2), InstructionBlock::new(vec![Box::new(affectation2)]), InstructionBlock::new(vec![Box::new(affectation3)]));
+    let condition_block = ConditionBlock::new_with_else_if(Box::new(condition1), InstructionBlock::new(vec![Box::new(affectation1)]), else_if);
+    let _ = interpreter.execute(&condition_block);
+    match interpreter._get_variable("a"){
+        Ok(v) => assert!(eq_values(&v,&Value::new_integer(2))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_function_call(){
+    let mut interpreter = get_interpreter();
+    let body = InstructionBlock::new(vec![
+        Box::new(Return::new(Box::new(Operation::new(Box::new(Variable::new("a")), Box::new(Variable::new("b")), '*'))))
+    ]);
+    let declaration = FunctionDeclaration::new(Function::new("mul", vec!["a".to_string(), "b".to_string()], body)).unwrap();
+    let _ = interpreter.execute(&declaration);
+    let call = FunctionCall::new(Box::new(Variable::new("mul")), vec![Box::new(IntegerValue::new(6)), Box::new(IntegerValue::new(7))]);
+    match interpreter.execute(&call) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(42))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_function_call_with_wrong_arity(){
+    let mut interpreter = get_interpreter();
+    let declaration = FunctionDeclaration::new(Function::new("nothing", vec![], InstructionBlock::new(vec![]))).unwrap();
+    let _ = interpreter.execute(&declaration);
+    let call = FunctionCall::new(Box::new(Variable::new("nothing")), vec![Box::new(IntegerValue::new(1))]);
+    match interpreter.execute(&call) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Function nothing expects 0 arguments but 1 were given")))
+    }
+}
+
+#[test]
+fn test_declare_native_function(){
+    let function = Function::new_native("double", Some(1), Box::new(|arguments| arguments[0].mul(&Value::new_integer(2))));
+    match FunctionDeclaration::new(function) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Native function double cannot be declared")))
+    }
+}
+
+#[test]
+fn test_call_non_function(){
+    let mut interpreter = get_interpreter();
+    let call = FunctionCall::new(Box::new(IntegerValue::new(3)), vec![]);
+    match interpreter.execute(&call) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot call Integer(3)")))
+    }
+}
+
+#[test]
+fn test_register_function(){
+    let mut interpreter = get_interpreter();
+    interpreter.register_function("double", 1, |arguments| arguments[0].mul(&Value::new_integer(2)));
+    let call = FunctionCall::new(Box::new(Variable::new("double")), vec![Box::new(IntegerValue::new(21))]);
+    match interpreter.execute(&call) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(42))),
+        Err(_) => assert!(false)
+    }
+    let call = FunctionCall::new(Box::new(Variable::new("double")), vec![]);
+    match interpreter.execute(&call) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Function double expects 1 arguments but 0 were given")))
+    }
+}
+
+#[test]
+fn test_register_variadic_function(){
+    let mut interpreter = get_interpreter();
+    interpreter.register_variadic_function("sum", |arguments| {
+        let mut total = Value::new_integer(0);
+        for argument in arguments {
+            total = total.add(argument)?;
+        }
+        Ok(total)
+    });
+    let call = FunctionCall::new(Box::new(Variable::new("sum")), vec![Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(2)), Box::new(IntegerValue::new(3))]);
+    match interpreter.execute(&call) {
+        Ok(value) => assert!(eq_values(&value, &Value::new_integer(6))),
+        Err(_) => assert!(false)
+    }
+    let call = FunctionCall::new(Box::new(Variable::new("sum")), vec![Box::new(IntegerValue::new(1)), Box::new(StringValue::new("a"))]);
+    match interpreter.execute(&call) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot add Integer(1) and String(a)")))
+    }
+}
+
+#[test]
+fn test_error_kinds(){
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Variable::new("missing")) {
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert_eq!(*e.get_kind(), ErrorKind::VariableNotFound{name: "missing".to_string()});
+            assert_eq!(e.to_string(), "VariableNotFoundError:Variable missing does not exist");
+        }
+    }
+    match interpreter.execute(&Operation::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(2)), '^')) {
+        Ok(_) => assert!(false),
+        Err(e) => assert_eq!(*e.get_kind(), ErrorKind::OperatorNotFound{operator: '^'})
+    }
+    match interpreter.execute(&Operation::new(Box::new(IntegerValue::new(1)), Box::new(StringValue::new("a")), '-')) {
+        Ok(_) => assert!(false),
+        Err(e) => assert_eq!(*e.get_kind(), ErrorKind::Operation)
+    }
+}
+
+#[test]
+fn test_integer_division_by_zero(){
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Operation::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(0)), '/')) {
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert_eq!(*e.get_kind(), ErrorKind::DivisionByZero);
+            assert_eq!(e.to_string(), "DivisionByZeroError:Division by zero");
+        }
+    }
+}
+
+fn execute_operation(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> Value {
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Operation::new(left, right, operator)) {
+        Ok(value) => value,
+        Err(e) => panic!("{}", e)
+    }
+}
+
+fn execute_condition(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> bool {
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Condition::new(left, right, operator)) {
+        Ok(Value::Boolean(b)) => b,
+        Ok(value) => panic!("{:?} is not a boolean", value),
+        Err(e) => panic!("{}", e)
+    }
+}
+
+#[test]
+fn test_mixed_arithmetic_promotes_to_float(){
+    // An integer combined with a float is promoted to a float, whichever side it is on.
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(2.5)), '+'), Value::Float(f) if f == 3.5));
+    assert!(matches!(execute_operation(Box::new(FloatValue::new(2.5)), Box::new(IntegerValue::new(1)), '+'), Value::Float(f) if f == 3.5));
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(5)), Box::new(FloatValue::new(0.5)), '-'), Value::Float(f) if f == 4.5));
+    assert!(matches!(execute_operation(Box::new(FloatValue::new(1.5)), Box::new(IntegerValue::new(2)), '*'), Value::Float(f) if f == 3.0));
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(2.0)), '/'), Value::Float(f) if f == 0.5));
+    // Two integers stay integers, the division truncating.
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(7)), Box::new(IntegerValue::new(2)), '/'), Value::Integer(3)));
+    // Numbers still do not mix with other types.
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Operation::new(Box::new(FloatValue::new(1.5)), Box::new(StringValue::new("a")), '+')) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot add Float(1.5) and String(a)")))
+    }
+}
+
+#[test]
+fn test_mixed_comparisons(){
+    assert!(execute_condition(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(1.5)), '<'));
+    assert!(execute_condition(Box::new(FloatValue::new(3.0)), Box::new(IntegerValue::new(2)), '>'));
+    assert!(execute_condition(Box::new(IntegerValue::new(2)), Box::new(FloatValue::new(2.0)), '≥'));
+    assert!(execute_condition(Box::new(FloatValue::new(2.0)), Box::new(IntegerValue::new(2)), '≤'));
+    assert!(!execute_condition(Box::new(IntegerValue::new(-1)), Box::new(FloatValue::new(-1.5)), '<'));
+    assert!(!execute_condition(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(f64::NAN)), '<'));
+    assert!(!execute_condition(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(f64::NAN)), '≥'));
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Condition::new(Box::new(IntegerValue::new(1)), Box::new(StringValue::new("a")), '<')) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot compare Integer(1) and String(a)")))
+    }
+}
+
+#[test]
+fn test_numeric_equality(){
+    // Numbers are equal when they have the same value, whatever their type.
+    assert!(execute_condition(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(1.0)), '='));
+    assert!(execute_condition(Box::new(FloatValue::new(1.0)), Box::new(IntegerValue::new(1)), '='));
+    assert!(execute_condition(Box::new(IntegerValue::new(1)), Box::new(FloatValue::new(1.5)), '≠'));
+    // The comparison is exact: 2^53 + 1 has no float representation and differs from the float 2^53.
+    assert!(execute_condition(Box::new(IntegerValue::new(9007199254740993)), Box::new(FloatValue::new(9007199254740992.0)), '≠'));
+    assert!(execute_condition(Box::new(IntegerValue::new(9007199254740993)), Box::new(FloatValue::new(9007199254740992.0)), '>'));
+    assert!(execute_condition(Box::new(IntegerValue::new(i64::MAX)), Box::new(FloatValue::new(9223372036854775808.0)), '<'));
+    // Equality never fails: values of other types are simply different.
+    assert!(execute_condition(Box::new(IntegerValue::new(1)), Box::new(StringValue::new("1")), '≠'));
+}
+
+fn execute_failing_operation(left: Box<dyn Valuable>, right: Box<dyn Valuable>, operator: char) -> CustomError {
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Operation::new(left, right, operator)) {
+        Ok(value) => panic!("{:?} should be an error", value),
+        Err(e) => e
+    }
+}
+
+#[test]
+fn test_checked_integer_arithmetic(){
+    let overflows = [
+        (i64::MAX, 1, '+'),
+        (i64::MIN, 1, '-'),
+        (i64::MAX, 2, '*'),
+        (i64::MIN, -1, '/'),
+    ];
+    for (left, right, operator) in overflows {
+        let e = execute_failing_operation(Box::new(IntegerValue::new(left)), Box::new(IntegerValue::new(right)), operator);
+        assert!(e._equals(&CustomError::new_integer_overflow_error()));
+        assert_eq!(e.get_code(), "E0011");
+    }
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&UnaryOperation::new(Box::new(IntegerValue::new(i64::MIN)), '-')) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_integer_overflow_error()))
+    }
+    // The limits themselves are still reachable.
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(i64::MAX - 1)), Box::new(IntegerValue::new(1)), '+'), Value::Integer(i64::MAX)));
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(i64::MIN)), Box::new(IntegerValue::new(1)), '/'), Value::Integer(i64::MIN)));
+}
+
+#[test]
+fn test_modulo(){
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(7)), Box::new(IntegerValue::new(3)), '%'), Value::Integer(1)));
+    // The remainder has the sign of the left operand.
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(-7)), Box::new(IntegerValue::new(3)), '%'), Value::Integer(-1)));
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(7)), Box::new(IntegerValue::new(-3)), '%'), Value::Integer(1)));
+    assert!(matches!(execute_operation(Box::new(FloatValue::new(7.5)), Box::new(IntegerValue::new(2)), '%'), Value::Float(f) if f == 1.5));
+    assert!(matches!(execute_operation(Box::new(IntegerValue::new(i64::MIN)), Box::new(IntegerValue::new(-1)), '%'), Value::Integer(0)));
+    let e = execute_failing_operation(Box::new(IntegerValue::new(7)), Box::new(IntegerValue::new(0)), '%');
+    assert!(e._equals(&CustomError::new_division_by_zero_error()));
+    let e = execute_failing_operation(Box::new(StringValue::new("a")), Box::new(IntegerValue::new(2)), '%');
+    assert!(e._equals(&CustomError::new_operation_error("Cannot compute the remainder of String(a) and Integer(2)")));
+}
+
+#[test]
+fn test_string_repetition_bounds(){
+    assert!(matches!(&execute_operation(Box::new(StringValue::new("ab")), Box::new(IntegerValue::new(0)), '*'), Value::String(s) if s.is_empty()));
+    let e = execute_failing_operation(Box::new(StringValue::new("ab")), Box::new(IntegerValue::new(-1)), '*');
+    assert!(e._equals(&CustomError::new_operation_error("Cannot repeat a string -1 times")));
+    let e = execute_failing_operation(Box::new(StringValue::new("ab")), Box::new(IntegerValue::new(i64::MAX)), '*');
+    assert!(e._equals(&CustomError::new_operation_error(&format!("Cannot repeat a string {} times, the result is too long", i64::MAX))));
+    // The length fits in an `isize` but no allocator can provide it.
+    let e = execute_failing_operation(Box::new(StringValue::new("ab")), Box::new(IntegerValue::new(4_000_000_000_000_000_000)), '*');
+    assert!(e._equals(&CustomError::new_operation_error("Cannot repeat a string 4000000000000000000 times, the result is too long")));
+}
+
+fn list_of(values: &[i64]) -> Box<ListValue> {
+    Box::new(ListValue::new(values.iter().map(|value| Box::new(IntegerValue::new(*value)) as Box<dyn Valuable>).collect()))
+}
+
+#[test]
+fn test_list_index(){
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&Affectation::new("xs", list_of(&[1, 2, 3]))) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    for (index, expected) in [(0, 1), (2, 3), (-1, 3), (-3, 1)] {
+        match interpreter.execute(&Index::new(Box::new(Variable::new("xs")), Box::new(IntegerValue::new(index)))) {
+            Ok(value) => assert!(matches!(value, Value::Integer(v) if v == expected)),
+            Err(_) => assert!(false)
+        }
+    }
+    for index in [3, -4] {
+        match interpreter.execute(&Index::new(Box::new(Variable::new("xs")), Box::new(IntegerValue::new(index)))) {
+            Ok(_) => assert!(false),
+            Err(e) => {
+                assert!(e._equals(&CustomError::new_index_error(index, 3)));
+                assert_eq!(e.get_kind(), &ErrorKind::Index{index, length: 3});
+            }
+        }
+    }
+    match interpreter.execute(&Index::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(0)))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot index Integer(1)")))
+    }
+    match interpreter.execute(&Index::new(Box::new(Variable::new("xs")), Box::new(StringValue::new("0")))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("List indexes must be integers, not String(0)")))
+    }
+}
+
+#[test]
+fn test_list_mutation_is_shared(){
+    let mut interpreter = get_interpreter();
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("xs", list_of(&[1, 2, 3]))),
+        Box::new(Affectation::new("ys", Box::new(Variable::new("xs")))),
+        Box::new(IndexAffectation::new(Box::new(Variable::new("ys")), Box::new(IntegerValue::new(-1)), Box::new(StringValue::new("last")))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("xs") {
+        Ok(value) => assert_eq!(value._to_string(), "[1, 2, \"last\"]"),
+        Err(_) => assert!(false)
+    }
+    match interpreter.execute(&IndexAffectation::new(Box::new(Variable::new("xs")), Box::new(IntegerValue::new(3)), Box::new(NullValue::new()))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_index_error(3, 3)))
+    }
+}
+
+#[test]
+fn test_list_concatenation_and_equality(){
+    let value = execute_operation(list_of(&[1, 2]), list_of(&[3]), '+');
+    assert!(value.eq(&Value::new_list(vec![Value::new_integer(1), Value::new_integer(2), Value::new_integer(3)])).unwrap());
+    assert!(value.eq(&Value::new_list(vec![Value::new_integer(1), Value::new_float(2.0), Value::new_integer(3)])).unwrap());
+    assert!(!value.eq(&Value::new_list(vec![Value::new_integer(1), Value::new_integer(2)])).unwrap());
+    let e = execute_failing_operation(list_of(&[1]), Box::new(IntegerValue::new(1)), '+');
+    assert!(e._equals(&CustomError::new_operation_error("Cannot add List([Integer(1)]) and Integer(1)")));
+}
+
+#[test]
+fn test_list_slice(){
+    let list = Value::new_list((0..5).map(Value::new_integer).collect());
+    let slices = [
+        (Value::new_integer(1), Value::new_integer(3), "[1, 2]"),
+        (Value::new_null(), Value::new_integer(-2), "[0, 1, 2]"),
+        (Value::new_integer(-2), Value::new_null(), "[3, 4]"),
+        (Value::new_null(), Value::new_null(), "[0, 1, 2, 3, 4]"),
+        (Value::new_integer(-100), Value::new_integer(100), "[0, 1, 2, 3, 4]"),
+        (Value::new_integer(4), Value::new_integer(1), "[]"),
+    ];
+    for (start, end, expected) in slices {
+        match list.slice(&start, &end) {
+            Ok(value) => assert_eq!(value._to_string(), expected),
+            Err(_) => assert!(false)
+        }
+    }
+    match list.slice(&Value::new_float(1.0), &Value::new_null()) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Slice bounds must be integers, not Float(1)")))
+    }
+}
+
+#[test]
+fn test_map_keys(){
+    let mut interpreter = get_interpreter();
+    let entries: Vec<(Box<dyn Valuable>, Box<dyn Valuable>)> = vec![
+        (Box::new(StringValue::new("a")), Box::new(IntegerValue::new(1))),
+        (Box::new(IntegerValue::new(2)), Box::new(StringValue::new("two"))),
+        (Box::new(StringValue::new("a")), Box::new(IntegerValue::new(3))),
+    ];
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("m", Box::new(MapValue::new(entries)))),
+        Box::new(IndexAffectation::new(Box::new(Variable::new("m")), Box::new(FloatValue::new(2.0)), Box::new(StringValue::new("deux")))),
+        Box::new(IndexAffectation::new(Box::new(Variable::new("m")), Box::new(BooleanValue::new(true)), Box::new(NullValue::new()))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    // A repeated key keeps its first position, and keys equal as values are the same key.
+    match interpreter._get_variable("m") {
+        Ok(value) => assert_eq!(value._to_string(), "{\"a\": 3, 2: \"deux\", true: null}"),
+        Err(_) => assert!(false)
+    }
+    match interpreter.execute(&Index::new(Box::new(Variable::new("m")), Box::new(IntegerValue::new(2)))) {
+        Ok(value) => assert!(matches!(&value, Value::String(s) if s == "deux")),
+        Err(_) => assert!(false)
+    }
+    match interpreter.execute(&Index::new(Box::new(Variable::new("m")), Box::new(StringValue::new("b")))) {
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_key_error("\"b\"")));
+            assert_eq!(e.to_string(), "KeyError:Key \"b\" not found");
+        }
+    }
+}
+
+#[test]
+fn test_map_equality(){
+    let map = |entries: &[(&str, i64)]| Value::new_map(entries.iter().map(|(key, value)| (Value::new_string(key), Value::new_integer(*value))).collect()).unwrap();
+    assert!(map(&[("a", 1), ("b", 2)]).eq(&map(&[("b", 2), ("a", 1)])).unwrap());
+    assert!(!map(&[("a", 1), ("b", 2)]).eq(&map(&[("a", 1), ("b", 3)])).unwrap());
+    assert!(!map(&[("a", 1)]).eq(&map(&[("a", 1), ("b", 2)])).unwrap());
+    assert!(!map(&[]).eq(&Value::new_list(Vec::new())).unwrap());
+}
+
+#[test]
+fn test_map_keys_of_any_type(){
+    let entries = vec![
+        (Value::new_integer(1), Value::new_string("one")),
+        (Value::new_float(1.0), Value::new_string("un")),
+        (Value::new_float(-0.0), Value::new_string("zero")),
+        (Value::new_float(2.5), Value::new_string("half")),
+        (Value::new_integer(9007199254740993), Value::new_string("odd")),
+        (Value::new_float(9007199254740992.0), Value::new_string("even")),
+        (Value::new_list(vec![Value::new_integer(1)]), Value::new_string("list")),
+        (Value::new_float(f64::NAN), Value::new_string("nan")),
+    ];
+    let map = Value::new_map(entries).unwrap();
+    assert_eq!(map._to_string(), "{1: \"un\", -0.0: \"zero\", 2.5: \"half\", 9007199254740993: \"odd\", 9007199254740992.0: \"even\", [1]: \"list\", NaN: \"nan\"}");
+    let expected = [
+        (Value::new_float(1.0), "un"),
+        (Value::new_integer(0), "zero"),
+        (Value::new_float(2.5), "half"),
+        (Value::new_integer(9007199254740992), "even"),
+        (Value::new_list(vec![Value::new_float(1.0)]), "list"),
+    ];
+    for (key, expected) in expected {
+        match map.get_index(&key) {
+            Ok(value) => assert_eq!(value._to_string(), expected),
+            Err(_) => assert!(false)
+        }
+    }
+    // NaN is equal to nothing, not even itself.
+    assert!(map.get_index(&Value::new_float(f64::NAN)).is_err());
+}
+
+#[test]
+fn test_map_as_its_own_key(){
+    let map = Value::new_map(Vec::new()).unwrap();
+    for value in [1, 2] {
+        match map.set_index(&map, Value::new_integer(value)) {
+            Ok(_) => (),
+            Err(_) => assert!(false)
+        }
+    }
+    match map.get_index(&map) {
+        Ok(value) => assert!(matches!(value, Value::Integer(2))),
+        Err(_) => assert!(false)
+    }
+    assert!(matches!(&map, Value::Map(entries) if entries.borrow().len() == 1));
+}
+
+#[test]
+fn test_values_containing_themselves(){
+    let list = |value: Value| Value::new_list(vec![value]);
+    let (a, b) = (list(Value::new_integer(0)), list(Value::new_integer(0)));
+    for xs in [&a, &b] {
+        match xs.set_index(&Value::new_integer(0), xs.clone()) {
+            Ok(_) => (),
+            Err(_) => assert!(false)
+        }
+    }
+    assert_eq!(a._to_string(), "[[...]]");
+    assert_eq!(format!("{:?}", a), "List([List([...])])");
+    assert!(a.eq(&a).unwrap());
+    match a.eq(&b) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_operation_error("Cannot compare values containing themselves")))
+    }
+    let map = Value::new_map(Vec::new()).unwrap();
+    match map.set_index(&Value::new_string("self"), map.clone()) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    assert_eq!(map._to_string(), "{\"self\": {...}}");
+}
+
+#[test]
+fn test_deeply_nested_values(){
+    let nest = || (0..200_000).fold(Value::new_list(Vec::new()), |xs, _| Value::new_list(vec![xs]));
+    let (a, b) = (nest(), nest());
+    assert!(a.eq(&b).unwrap());
+    assert_eq!(a._to_string().len(), 400_002);
+    assert_eq!(format!("{:?}", b).len(), 1_600_008);
+    drop(a);
+    drop(b);
+}
+
+#[test]
+fn test_for_loop_over_range(){
+    let mut interpreter = get_interpreter();
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("total", Box::new(IntegerValue::new(0)))),
+        Box::new(ForLoop::new("i", Box::new(RangeValue::new(Box::new(IntegerValue::new(-2)), Box::new(IntegerValue::new(5)))), InstructionBlock::new(vec![
+            Box::new(Affectation::new("total", Box::new(Operation::new(Box::new(Variable::new("total")), Box::new(Variable::new("i")), '+')))),
+            Box::new(Affectation::new("inner", Box::new(Variable::new("i")))),
+        ]))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("total") {
+        Ok(value) => assert!(matches!(value, Value::Integer(7))),
+        Err(_) => assert!(false)
+    }
+    // The loop variable and the variables created by the body only live during an iteration.
+    for name in ["i", "inner"] {
+        match interpreter._get_variable(name) {
+            Ok(_) => assert!(false),
+            Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error(name)))
+        }
+    }
+}
+
+#[test]
+fn test_for_loop_over_map(){
+    let mut interpreter = get_interpreter();
+    let entries: Vec<(Box<dyn Valuable>, Box<dyn Valuable>)> = vec![
+        (Box::new(StringValue::new("b")), Box::new(IntegerValue::new(1))),
+        (Box::new(StringValue::new("a")), Box::new(IntegerValue::new(2))),
+    ];
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("keys", Box::new(StringValue::new("")))),
+        Box::new(Affectation::new("sum", Box::new(IntegerValue::new(0)))),
+        Box::new(ForLoop::new_with_value("k", "v", Box::new(MapValue::new(entries)), InstructionBlock::new(vec![
+            Box::new(Affectation::new("keys", Box::new(Operation::new(Box::new(Variable::new("keys")), Box::new(Variable::new("k")), '+')))),
+            Box::new(Affectation::new("sum", Box::new(Operation::new(Box::new(Variable::new("sum")), Box::new(Variable::new("v")), '+')))),
+        ]))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match (interpreter._get_variable("keys"), interpreter._get_variable("sum")) {
+        (Ok(keys), Ok(sum)) => assert!(matches!((&keys, &sum), (Value::String(k), Value::Integer(3)) if k == "ba")),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_for_loop_errors(){
+    let mut interpreter = get_interpreter();
+    match interpreter.execute(&ForLoop::new("x", Box::new(IntegerValue::new(3)), InstructionBlock::new(vec![]))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot iterate over Integer(3)")))
+    }
+    match interpreter.execute(&ForLoop::new_with_value("k", "v", list_of(&[1]), InstructionBlock::new(vec![]))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Cannot iterate over List([Integer(1)]) with 2 variables, only maps give a key and a value")))
+    }
+    match interpreter.execute(&RangeValue::new(Box::new(IntegerValue::new(0)), Box::new(StringValue::new("3")))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_type_error("Range bounds must be integers, not Integer(0) and String(3)")))
+    }
+}
+
+#[test]
+fn test_range_iteration_is_lazy(){
+    let range = Value::new_range(&Value::new_integer(i64::MAX - 2), &Value::new_integer(i64::MAX)).unwrap();
+    let elements: Vec<String> = range.iterate(1).unwrap().map(|(element, _)| element._to_string()).collect();
+    assert_eq!(elements, vec![(i64::MAX - 2).to_string(), (i64::MAX - 1).to_string()]);
+    let mut huge = Value::new_range(&Value::new_integer(0), &Value::new_integer(i64::MAX)).unwrap().iterate(1).unwrap();
+    assert!(matches!(huge.next(), Some((Value::Integer(0), None))));
+    assert!(Value::new_range(&Value::new_integer(5), &Value::new_integer(1)).unwrap().iterate(1).unwrap().next().is_none());
+}
+
+#[test]
+fn test_break_and_continue(){
+    let mut interpreter = get_interpreter();
+    // Sums the odd numbers below 10, leaving the loop from two blocks deep.
+    let is_even = Condition::new(Box::new(Operation::new(Box::new(Variable::new("i")), Box::new(IntegerValue::new(2)), '%')), Box::new(IntegerValue::new(0)), '=');
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("total", Box::new(IntegerValue::new(0)))),
+        Box::new(Affectation::new("i", Box::new(IntegerValue::new(0)))),
+        Box::new(ConditionLoop::new(Box::new(BooleanValue::new(true)), InstructionBlock::new(vec![
+            Box::new(Affectation::new("i", Box::new(Operation::new(Box::new(Variable::new("i")), Box::new(IntegerValue::new(1)), '+')))),
+            Box::new(InstructionBlock::new(vec![
+                Box::new(ConditionBlock::new(Box::new(Condition::new(Box::new(Variable::new("i")), Box::new(IntegerValue::new(10)), '>')), InstructionBlock::new(vec![Box::new(Break)]))),
+            ])),
+            Box::new(ConditionBlock::new(Box::new(is_even), InstructionBlock::new(vec![Box::new(Continue)]))),
+            Box::new(Affectation::new("total", Box::new(Operation::new(Box::new(Variable::new("total")), Box::new(Variable::new("i")), '+')))),
+        ]))),
+        Box::new(ForLoop::new("x", list_of(&[1, 2, 3]), InstructionBlock::new(vec![
+            Box::new(Affectation::new("last", Box::new(Variable::new("x")))),
+            Box::new(Break),
+        ]))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match (interpreter._get_variable("total"), interpreter._get_variable("i")) {
+        (Ok(total), Ok(i)) => assert!(matches!((total, i), (Value::Integer(25), Value::Integer(11)))),
+        _ => assert!(false)
+    }
+    // Every scope opened by the loops was closed: `last` only lived in the body.
+    match interpreter._get_variable("last") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("last")))
+    }
+}
+
+#[test]
+fn test_try_catch(){
+    let mut interpreter = get_interpreter();
+    let failing = || InstructionBlock::new(vec![
+        Box::new(InstructionBlock::new(vec![
+            Box::new(Affectation::new("inner", Box::new(IntegerValue::new(1)))),
+            Box::new(Affectation::new("x", Box::new(Operation::new(Box::new(IntegerValue::new(1)), Box::new(IntegerValue::new(0)), '/')))),
+        ])),
+    ]);
+    let instructions: Vec<Box<dyn Instruction>> = vec![
+        Box::new(Affectation::new("kind", Box::new(NullValue::new()))),
+        Box::new(Affectation::new("thrown", Box::new(NullValue::new()))),
+        Box::new(Affectation::new("cleaned", Box::new(BooleanValue::new(false)))),
+        Box::new(TryBlock::new_with_finally(failing(), "e", InstructionBlock::new(vec![
+            Box::new(Affectation::new("kind", Box::new(Index::new(Box::new(Variable::new("e")), Box::new(StringValue::new("kind")))))),
+        ]), InstructionBlock::new(vec![
+            Box::new(Affectation::new("cleaned", Box::new(BooleanValue::new(true)))),
+        ]))),
+        Box::new(TryBlock::new(InstructionBlock::new(vec![Box::new(Throw::new(list_of(&[1, 2])))]), "e", InstructionBlock::new(vec![
+            Box::new(Affectation::new("thrown", Box::new(Variable::new("e")))),
+        ]))),
+    ];
+    match interpreter.execute_instructions(&instructions) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    let expected = [("kind", "DivisionByZeroError"), ("thrown", "[1, 2]"), ("cleaned", "true")];
+    for (name, value) in expected {
+        match interpreter._get_variable(name) {
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+    // The scopes of the blocks left by the error, and the one holding the error, were closed.
+    for name in ["inner", "e"] {
+        match interpreter._get_variable(name) {
+            Ok(_) => assert!(false),
+            Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error(name)))
+        }
+    }
+    // Without a handler, the error is raised again once the finally block has run.
+    match interpreter.execute(&TryBlock::new_without_catch(failing(), InstructionBlock::new(vec![
+        Box::new(Affectation::new("cleaned", Box::new(StringValue::new("again")))),
+    ]))) {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_division_by_zero_error()))
+    }
+    match interpreter._get_variable("cleaned") {
+        Ok(v) => assert_eq!(v._to_string(), "again"),
+        Err(_) => assert!(false)
+    }
+    match interpreter.execute(&Throw::new(Box::new(StringValue::new("boom")))) {
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_thrown_error(Value::new_string("boom"))));
+            assert!(matches!(&e.into_value(), Value::String(s) if s == "boom"));
+        }
+    }
+}
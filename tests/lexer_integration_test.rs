@@ -1,7 +1,8 @@
 extern crate krab_language;
 
 use krab_language::errors::CustomError;
-use krab_language::lexer::{Lexer, Token};
+use krab_language::lexer::{Lexer, Token, TokenKind, Op, Cmp, Logic, Kw};
+use krab_language::span::Span;
 
 fn compare_tokens(tokens: Vec<Token>, expected_tokens: Vec<Token>) -> bool {
     if tokens.len() != expected_tokens.len() {
@@ -18,11 +19,12 @@ fn compare_tokens(tokens: Vec<Token>, expected_tokens: Vec<Token>) -> bool {
 #[test]
 fn affectation_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"a = 20".to_string());
+    let tokens = lexer.lex("a = 20");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_assign("="),
-        Token::new_number("20")
+        Token::new_number("20"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -33,10 +35,11 @@ fn affectation_test() {
 #[test]
 fn string_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"\"hello world\" \"ok\"".to_string());
+    let tokens = lexer.lex("\"hello world\" \"ok\"");
     let expected_tokens = vec![
         Token::new_string("hello world"),
-        Token::new_string("ok")
+        Token::new_string("ok"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -47,11 +50,12 @@ fn string_test() {
 #[test]
 fn number_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"123 456 78.9".to_string());
+    let tokens = lexer.lex("123 456 78.9");
     let expected_tokens = vec![
         Token::new_number("123"),
         Token::new_number("456"),
-        Token::new_number("78.9")
+        Token::new_number("78.9"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -62,14 +66,15 @@ fn number_test() {
 #[test]
 fn operator_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"+ - * /    ++".to_string());
+    let tokens = lexer.lex("+ - * /    ++");
     let expected_tokens = vec![
         Token::new_operator("+"),
         Token::new_operator("-"),
         Token::new_operator("*"),
         Token::new_operator("/"),
         Token::new_operator("+"),
-        Token::new_operator("+")
+        Token::new_operator("+"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -80,11 +85,12 @@ fn operator_test() {
 #[test]
 fn identifier_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"hello_2 world158 ___r___".to_string());
+    let tokens = lexer.lex("hello_2 world158 ___r___");
     let expected_tokens = vec![
         Token::new_identifier("hello_2"),
         Token::new_identifier("world158"),
-        Token::new_identifier("___r___")
+        Token::new_identifier("___r___"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -95,7 +101,7 @@ fn identifier_test() {
 #[test]
 fn parenthesis_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"(a + b) * (c - d)".to_string());
+    let tokens = lexer.lex("(a + b) * (c - d)");
     let expected_tokens = vec![
         Token::new_parenthesis("("),
         Token::new_identifier("a"),
@@ -107,7 +113,8 @@ fn parenthesis_test() {
         Token::new_identifier("c"),
         Token::new_operator("-"),
         Token::new_identifier("d"),
-        Token::new_parenthesis(")")
+        Token::new_parenthesis(")"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -118,7 +125,7 @@ fn parenthesis_test() {
 #[test]
 fn semicolon_test() {
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"a = 20; b = 30;".to_string());
+    let tokens = lexer.lex("a = 20; b = 30;");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_assign("="),
@@ -127,7 +134,8 @@ fn semicolon_test() {
         Token::new_identifier("b"),
         Token::new_assign("="),
         Token::new_number("30"),
-        Token::new_semicolon()
+        Token::new_semicolon(),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -138,14 +146,15 @@ fn semicolon_test() {
 #[test]
 fn invalid_character(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"ù".to_string());
+    let tokens = lexer.lex("ù");
     match tokens {
         Ok(_) => assert!(false),
         Err(err) => assert!(err._equals(&CustomError::new_lexer_error("Unknown character: ù")))
     }
-    let tokens = lexer.lex(&"\"ù\"".to_string());
+    let tokens = lexer.lex("\"ù\"");
     let expected_tokens = vec![
-        Token::new_string("ù")
+        Token::new_string("ù"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(_) => assert!(compare_tokens(tokens.unwrap(), expected_tokens)),
@@ -156,7 +165,7 @@ fn invalid_character(){
 #[test]
 fn invalid_string(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"\"hello".to_string());
+    let tokens = lexer.lex("\"hello");
     match tokens {
         Ok(_) => assert!(false),
         Err(err) => assert!(err._equals(&CustomError::new_lexer_error("String not closed")))
@@ -166,7 +175,7 @@ fn invalid_string(){
 #[test]
 fn invalid_number(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"123.456.789".to_string());
+    let tokens = lexer.lex("123.456.789");
     match tokens {
         Ok(_) => assert!(false),
         Err(err) => assert!(err._equals(&CustomError::new_lexer_error("Invalid number")))
@@ -176,41 +185,45 @@ fn invalid_number(){
 #[test]
 fn test_comparator(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"a > b".to_string());
+    let tokens = lexer.lex("a > b");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_comparator(">"),
-        Token::new_identifier("b")
+        Token::new_identifier("b"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
         Err(_) => assert!(false)
     }
-    let tokens = lexer.lex(&"a >= b".to_string());
+    let tokens = lexer.lex("a >= b");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_comparator(">="),
-        Token::new_identifier("b")
+        Token::new_identifier("b"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
         Err(_) => assert!(false)
     }
-    let tokens = lexer.lex(&"a < b".to_string());
+    let tokens = lexer.lex("a < b");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_comparator("<"),
-        Token::new_identifier("b")
+        Token::new_identifier("b"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
         Err(_) => assert!(false)
     }
-    let tokens = lexer.lex(&"a <= b".to_string());
+    let tokens = lexer.lex("a <= b");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_comparator("<="),
-        Token::new_identifier("b")
+        Token::new_identifier("b"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -221,13 +234,14 @@ fn test_comparator(){
 #[test]
 fn test_bracket(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"{a = 20}".to_string());
+    let tokens = lexer.lex("{a = 20}");
     let expected_tokens = vec![
         Token::new_bracket("{"),
         Token::new_identifier("a"),
         Token::new_assign("="),
         Token::new_number("20"),
-        Token::new_bracket("}")
+        Token::new_bracket("}"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -238,7 +252,7 @@ fn test_bracket(){
 #[test]
 fn test_while(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"while a < b { a = a + 1; }".to_string());
+    let tokens = lexer.lex("while a < b { a = a + 1; }");
     let expected_tokens = vec![
         Token::new_keyword("while"),
         Token::new_identifier("a"),
@@ -251,7 +265,8 @@ fn test_while(){
         Token::new_operator("+"),
         Token::new_number("1"),
         Token::new_semicolon(),
-        Token::new_bracket("}")
+        Token::new_bracket("}"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -262,14 +277,15 @@ fn test_while(){
 #[test]
 fn test_line_feed(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"a = 20\nb = 30".to_string());
+    let tokens = lexer.lex("a = 20\nb = 30");
     let expected_tokens = vec![
         Token::new_identifier("a"),
         Token::new_assign("="),
         Token::new_number("20"),
         Token::new_identifier("b"),
         Token::new_assign("="),
-        Token::new_number("30")
+        Token::new_number("30"),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
@@ -280,7 +296,7 @@ fn test_line_feed(){
 #[test]
 fn test_else(){
     let lexer = Lexer::new();
-    let tokens = lexer.lex(&"if a > b { a = a + 1; } else { a = a - 1; };".to_string());
+    let tokens = lexer.lex("if a > b { a = a + 1; } else { a = a - 1; };");
     let expected_tokens = vec![
         Token::new_keyword("if"),
         Token::new_identifier("a"),
@@ -303,10 +319,270 @@ fn test_else(){
         Token::new_number("1"),
         Token::new_semicolon(),
         Token::new_bracket("}"),
-        Token::new_semicolon()
+        Token::new_semicolon(),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
+        Err(_) => assert!(false)
+    }
+}
+#[test]
+fn test_equality_comparators(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("a == b != c = d");
+    let expected_tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_comparator("=="),
+        Token::new_identifier("b"),
+        Token::new_comparator("!="),
+        Token::new_identifier("c"),
+        Token::new_assign("="),
+        Token::new_identifier("d"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_unary_operators(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("x = -5; !b");
+    let expected_tokens = vec![
+        Token::new_identifier("x"),
+        Token::new_assign("="),
+        Token::new_operator("-"),
+        Token::new_number("5"),
+        Token::new_semicolon(),
+        Token::new_operator("!"),
+        Token::new_identifier("b"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_literal_keywords(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("true false null nullable");
+    let expected_tokens = vec![
+        Token::new_keyword("true"),
+        Token::new_keyword("false"),
+        Token::new_keyword("null"),
+        Token::new_identifier("nullable"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_logical_operators(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("a and b or not c && d || e");
+    let expected_tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_logical("and"),
+        Token::new_identifier("b"),
+        Token::new_logical("or"),
+        Token::new_logical("not"),
+        Token::new_identifier("c"),
+        Token::new_logical("&&"),
+        Token::new_identifier("d"),
+        Token::new_logical("||"),
+        Token::new_identifier("e"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_function_declaration(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("fn add(a, b) { return a + b; };");
+    let expected_tokens = vec![
+        Token::new_keyword("fn"),
+        Token::new_identifier("add"),
+        Token::new_parenthesis("("),
+        Token::new_identifier("a"),
+        Token::new_comma(),
+        Token::new_identifier("b"),
+        Token::new_parenthesis(")"),
+        Token::new_bracket("{"),
+        Token::new_keyword("return"),
+        Token::new_identifier("a"),
+        Token::new_operator("+"),
+        Token::new_identifier("b"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_semicolon(),
+        Token::new_eof()
     ];
     match tokens {
         Ok(tokens) => assert!(compare_tokens(tokens, expected_tokens)),
         Err(_) => assert!(false)
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_token_spans(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("a = 12;\n  b = \"é\" + a;").unwrap();
+    let expected = vec![
+        Span::new(0, 1, 1, 1),
+        Span::new(2, 3, 1, 3),
+        Span::new(4, 6, 1, 5),
+        Span::new(6, 7, 1, 7),
+        Span::new(10, 11, 2, 3),
+        Span::new(12, 13, 2, 5),
+        Span::new(14, 18, 2, 7),
+        Span::new(19, 20, 2, 11),
+        Span::new(21, 22, 2, 13),
+        Span::new(22, 23, 2, 14),
+        Span::new(23, 23, 2, 15),
+    ];
+    assert_eq!(tokens.len(), expected.len());
+    for i in 0..tokens.len() {
+        assert_eq!(tokens[i].get_span(), Some(expected[i]));
+    }
+}
+
+#[test]
+fn test_lexer_error_span(){
+    let lexer = Lexer::new();
+    match lexer.lex("a = 1;\nb = 2 $ 3;") {
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_lexer_error("Unknown character: $")));
+            assert_eq!(e.get_span(), Some(&Span::new(13, 14, 2, 7)));
+        }
+    }
+    match lexer.lex("a = \"abc;") {
+        Ok(_) => assert!(false),
+        Err(e) => assert_eq!(e.get_span(), Some(&Span::new(4, 5, 1, 5)))
+    }
+}
+
+#[test]
+fn test_lex_with_diagnostics(){
+    let lexer = Lexer::new();
+    let (tokens, errors) = lexer.lex_with_diagnostics("a = 1 $ 2;\nb = 1.2.3 @;");
+    let expected_tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_number("1"),
+        Token::new_number("2"),
+        Token::new_semicolon(),
+        Token::new_identifier("b"),
+        Token::new_assign("="),
+        Token::new_semicolon(),
+        Token::new_eof()
+    ];
+    assert!(compare_tokens(tokens, expected_tokens));
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0]._equals(&CustomError::new_lexer_error("Unknown character: $")));
+    assert!(errors[1]._equals(&CustomError::new_lexer_error("Invalid number")));
+    assert!(errors[2]._equals(&CustomError::new_lexer_error("Unknown character: @")));
+    match lexer.lex("a = 1 $ 2 @;") {
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_lexer_error("Unknown character: $")))
+    }
+}
+
+#[test]
+fn test_token_kinds(){
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("if (x >= 1.5) && y != 2 { return -x; }").unwrap();
+    let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.get_kind()).collect();
+    assert_eq!(kinds, vec![
+        TokenKind::Keyword(Kw::If),
+        TokenKind::LParen,
+        TokenKind::Identifier,
+        TokenKind::Comparator(Cmp::Ge),
+        TokenKind::Float,
+        TokenKind::RParen,
+        TokenKind::Logical(Logic::And),
+        TokenKind::Identifier,
+        TokenKind::Comparator(Cmp::Ne),
+        TokenKind::Integer,
+        TokenKind::LBrace,
+        TokenKind::Keyword(Kw::Return),
+        TokenKind::Operator(Op::Sub),
+        TokenKind::Identifier,
+        TokenKind::Semicolon,
+        TokenKind::RBrace,
+        TokenKind::Eof,
+    ]);
+    assert!(Token::new_number("12").is(TokenKind::Integer));
+    assert!(Token::new_number("1.2").is(TokenKind::Float));
+}
+
+#[test]
+fn list_test() {
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("xs[1:] = [a, 2]");
+    let expected_tokens = vec![
+        Token::new_identifier("xs"),
+        Token::new_square_bracket("["),
+        Token::new_number("1"),
+        Token::new_colon(),
+        Token::new_square_bracket("]"),
+        Token::new_assign("="),
+        Token::new_square_bracket("["),
+        Token::new_identifier("a"),
+        Token::new_comma(),
+        Token::new_number("2"),
+        Token::new_square_bracket("]"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => {
+            assert!(tokens[1].is(TokenKind::LBracket) && tokens[3].is(TokenKind::Colon) && tokens[4].is(TokenKind::RBracket));
+            assert!(compare_tokens(tokens, expected_tokens));
+        },
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn range_test() {
+    let lexer = Lexer::new();
+    let tokens = lexer.lex("for i in 0..1_000_000 { x = 1.5..n; }");
+    let expected_tokens = vec![
+        Token::new_keyword("for"),
+        Token::new_identifier("i"),
+        Token::new_keyword("in"),
+        Token::new_number("0"),
+        Token::new_dot_dot(),
+        Token::new_number("1000000"),
+        Token::new_bracket("{"),
+        Token::new_identifier("x"),
+        Token::new_assign("="),
+        Token::new_number("1.5"),
+        Token::new_dot_dot(),
+        Token::new_identifier("n"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_eof()
+    ];
+    match tokens {
+        Ok(tokens) => {
+            assert!(tokens[0].is(TokenKind::Keyword(Kw::For)) && tokens[2].is(TokenKind::Keyword(Kw::In)));
+            assert!(tokens[4].is(TokenKind::DotDot) && tokens[5].is(TokenKind::Integer));
+            assert_eq!(tokens[5].get_span().map(|span| (span.get_start(), span.get_end())), Some((12, 21)));
+            assert!(compare_tokens(tokens, expected_tokens));
+        },
+        Err(_) => assert!(false)
+    }
+}
@@ -1,20 +1,26 @@
 extern crate krab_language;
-use krab_language::lexer::Token;
+use std::env;
+use krab_language::lexer::{Lexer, Token};
 use krab_language::parser::Parser;
-use krab_language::interpreter::Interpreter;
+use krab_language::interpreter::{Engine, Interpreter, Operation, Variable, IntegerValue, Valuable};
+use krab_language::ast::{Expr, Slot, Stmt};
 use krab_language::value::Value;
+use krab_language::errors::CustomError;
+
+/// Interpreter on the engine named by `KRAB_ENGINE`, so that the tests can be run against either engine.
+fn get_interpreter() -> Interpreter {
+    let engine = env::var("KRAB_ENGINE").ok().and_then(|name| Engine::from_name(&name));
+    Interpreter::with_engine(engine.unwrap_or(Engine::TreeWalker))
+}
 
 fn eq_values(value1: &Value, value2: &Value) -> bool {
-    match value1.eq(value2) {
-        Ok(b) => b,
-        Err(_) => false
-    }
+    value1.eq(value2).unwrap_or_default()
 }
 
 #[test]
 fn test_affectation(){
     let parser = Parser::new();
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = get_interpreter();
     let tokens = vec![
         Token::new_identifier("a"),
         Token::new_assign("="),
@@ -40,7 +46,7 @@ fn test_affectation(){
 #[test]
 fn test_condition_loop(){
     let parser = Parser::new();
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = get_interpreter();
     let tokens = vec![
         Token::new_identifier("i"),
         Token::new_assign("="),
@@ -80,7 +86,7 @@ fn test_condition_loop(){
 #[test]
 fn test_condition_block_with_else(){
     let parser = Parser::new();
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = get_interpreter();
     let tokens = vec![
         Token::new_identifier("i"),
         Token::new_assign("="),
@@ -122,4 +128,988 @@ fn test_condition_block_with_else(){
         },
         Err(_) => assert!(false)
     }
-}
\ No newline at end of file
+}
+fn execute_tokens(tokens: Vec<Token>) -> Interpreter {
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    match parser.parse_instructions(tokens){
+        Ok(instructions) => {
+            match interpreter.execute_instructions(&instructions){
+                Ok(_) => {},
+                Err(_) => assert!(false)
+            }
+        },
+        Err(_) => assert!(false)
+    }
+    interpreter
+}
+
+#[test]
+fn test_operator_precedence(){
+    let tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_number("2"),
+        Token::new_operator("+"),
+        Token::new_number("3"),
+        Token::new_operator("*"),
+        Token::new_number("4"),
+        Token::new_operator("-"),
+        Token::new_number("1"),
+        Token::new_semicolon()
+    ];
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(13))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_left_associativity(){
+    let tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_number("20"),
+        Token::new_operator("-"),
+        Token::new_number("5"),
+        Token::new_operator("-"),
+        Token::new_number("3"),
+        Token::new_operator("/"),
+        Token::new_number("3"),
+        Token::new_semicolon()
+    ];
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(14))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_parenthesis(){
+    let tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_parenthesis("("),
+        Token::new_number("2"),
+        Token::new_operator("+"),
+        Token::new_number("3"),
+        Token::new_parenthesis(")"),
+        Token::new_operator("*"),
+        Token::new_parenthesis("("),
+        Token::new_parenthesis("("),
+        Token::new_number("4"),
+        Token::new_parenthesis(")"),
+        Token::new_operator("-"),
+        Token::new_number("1"),
+        Token::new_parenthesis(")"),
+        Token::new_semicolon()
+    ];
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(15))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_comparison_of_expressions(){
+    let tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_number("2"),
+        Token::new_operator("*"),
+        Token::new_number("3"),
+        Token::new_comparator(">"),
+        Token::new_number("1"),
+        Token::new_operator("+"),
+        Token::new_number("4"),
+        Token::new_semicolon()
+    ];
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Boolean(true))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_unclosed_parenthesis(){
+    let parser = Parser::new();
+    let tokens = vec![
+        Token::new_identifier("a"),
+        Token::new_assign("="),
+        Token::new_parenthesis("("),
+        Token::new_number("2"),
+        Token::new_operator("+"),
+        Token::new_number("3"),
+        Token::new_semicolon()
+    ];
+    match parser.parse_instructions(tokens){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_parser_error("')' expected but none found")))
+    }
+}
+
+#[test]
+fn test_all_operators_and_comparators(){
+    let lexer = Lexer::new();
+    let source = "a = 10 - 4 / 2 * 3; b = a >= 4; c = a <= 3; d = a == 4; e = a != 4;";
+    let tokens = match lexer.lex(source) {
+        Ok(tokens) => tokens,
+        Err(_) => {assert!(false); return;}
+    };
+    let mut interpreter = execute_tokens(tokens);
+    let expected = vec![
+        ("a", Value::Integer(4)),
+        ("b", Value::Boolean(true)),
+        ("c", Value::Boolean(false)),
+        ("d", Value::Boolean(true)),
+        ("e", Value::Boolean(false)),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert!(eq_values(&v, &value)),
+            Err(_) => assert!(false)
+        }
+    }
+}
+
+#[test]
+fn test_unary_operators(){
+    let lexer = Lexer::new();
+    let source = "a = -5; b = -a * 2 - -3; c = !(a > b); d = -9223372036854775808;";
+    let tokens = match lexer.lex(source) {
+        Ok(tokens) => tokens,
+        Err(_) => {assert!(false); return;}
+    };
+    let mut interpreter = execute_tokens(tokens);
+    let expected = vec![
+        ("a", Value::Integer(-5)),
+        ("b", Value::Integer(13)),
+        ("c", Value::Boolean(true)),
+        ("d", Value::Integer(i64::MIN)),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert!(eq_values(&v, &value)),
+            Err(_) => assert!(false)
+        }
+    }
+}
+
+#[test]
+fn test_boolean_and_null_literals(){
+    let tokens = vec![
+        Token::new_identifier("found"),
+        Token::new_assign("="),
+        Token::new_keyword("false"),
+        Token::new_semicolon(),
+        Token::new_identifier("result"),
+        Token::new_assign("="),
+        Token::new_keyword("null"),
+        Token::new_semicolon(),
+        Token::new_keyword("if"),
+        Token::new_operator("!"),
+        Token::new_identifier("found"),
+        Token::new_bracket("{"),
+        Token::new_identifier("found"),
+        Token::new_assign("="),
+        Token::new_keyword("true"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_semicolon()
+    ];
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("found"){
+        Ok(value) => assert!(eq_values(&value, &Value::Boolean(true))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("result"){
+        Ok(Value::Null()) => {},
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_logical_operators_in_loop(){
+    let lexer = Lexer::new();
+    let source = "i = 0; n = 10; ok = true;
+        while i < n and ok {
+            i = i + 1;
+            if i == 4 || not i < 100 { ok = false; };
+        };
+        a = not 1 == 2 and !false;";
+    let tokens = match lexer.lex(source) {
+        Ok(tokens) => tokens,
+        Err(_) => {assert!(false); return;}
+    };
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("i"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(4))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Boolean(true))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_not_and_bang_precedence(){
+    // `not` negates the whole comparison, `!` only the value right after it.
+    let mut interpreter = execute_source("a = not false == null; b = !false == null;");
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Boolean(true))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("b"){
+        Ok(value) => assert!(eq_values(&value, &Value::Boolean(false))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_condition_block_with_else_if(){
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    let tokens = vec![
+        Token::new_identifier("i"),
+        Token::new_assign("="),
+        Token::new_number("5"),
+        Token::new_semicolon(),
+        Token::new_keyword("if"),
+        Token::new_identifier("i"),
+        Token::new_comparator(">"),
+        Token::new_number("10"),
+        Token::new_bracket("{"),
+        Token::new_identifier("i"),
+        Token::new_assign("="),
+        Token::new_number("1"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_keyword("else"),
+        Token::new_keyword("if"),
+        Token::new_identifier("i"),
+        Token::new_comparator(">"),
+        Token::new_number("3"),
+        Token::new_bracket("{"),
+        Token::new_identifier("i"),
+        Token::new_assign("="),
+        Token::new_number("2"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_keyword("else"),
+        Token::new_bracket("{"),
+        Token::new_identifier("i"),
+        Token::new_assign("="),
+        Token::new_number("3"),
+        Token::new_semicolon(),
+        Token::new_bracket("}"),
+        Token::new_semicolon()
+    ];
+    match parser.parse_instructions(tokens){
+        Ok(instructions) => {
+            match interpreter.execute_instructions(&instructions){
+                Ok(_) => {
+                    match interpreter._get_variable("i"){
+                        Ok(value) => assert!(eq_values(&value, &Value::Integer(2))),
+                        Err(_) => assert!(false)
+                    }
+                }
+                Err(_) => assert!(false)
+            }
+        },
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_else_if_chain_without_else(){
+    let lexer = Lexer::new();
+    let source = "i = 0; r = 0;
+        while i < 4 {
+            if i == 0 { r = r + 1; }
+            else if i == 1 { r = r + 10; }
+            else if i == 2 { r = r + 100; };
+            i = i + 1;
+        };";
+    let tokens = match lexer.lex(source) {
+        Ok(tokens) => tokens,
+        Err(_) => {assert!(false); return;}
+    };
+    let mut interpreter = execute_tokens(tokens);
+    match interpreter._get_variable("r"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(111))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_else_without_block(){
+    let parser = Parser::new();
+    let tokens = vec![
+        Token::new_keyword("if"),
+        Token::new_keyword("true"),
+        Token::new_bracket("{"),
+        Token::new_bracket("}"),
+        Token::new_keyword("else"),
+        Token::new_identifier("i"),
+        Token::new_semicolon()
+    ];
+    match parser.parse_instructions(tokens){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_parser_error("Expected { after else")))
+    }
+}
+
+fn execute_source(source: &str) -> Interpreter {
+    let lexer = Lexer::new();
+    match lexer.lex(source) {
+        Ok(tokens) => execute_tokens(tokens),
+        Err(_) => {assert!(false); get_interpreter()}
+    }
+}
+
+#[test]
+fn test_function_declaration_and_call(){
+    let mut interpreter = execute_source("
+        fn add(a, b) { return a + b; };
+        fn nothing() { };
+        x = add(2, 3) * add(1, 1);
+        y = nothing();
+        other = add;
+        z = other(10, -1);");
+    match interpreter._get_variable("x"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(10))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("y"){
+        Ok(Value::Null()) => {},
+        _ => assert!(false)
+    }
+    match interpreter._get_variable("z"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(9))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_recursive_function(){
+    let mut interpreter = execute_source("
+        fn fib(n) {
+            if n < 2 { return n; };
+            return fib(n - 1) + fib(n - 2);
+        };
+        r = fib(15);");
+    match interpreter._get_variable("r"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(610))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_return_unwinds_nested_blocks(){
+    let mut interpreter = execute_source("
+        fn find(limit) {
+            i = 0;
+            while true {
+                if i * i > limit { return i; };
+                i = i + 1;
+            };
+        };
+        r = find(50);
+        s = find(0);");
+    match interpreter._get_variable("r"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(8))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("s"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(1))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_function_scope(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let mut interpreter = execute_source("
+        total = 0;
+        fn add_to_total(n) { total = total + n; local = n; };
+        add_to_total(5);
+        fn read_local() { return secret; };");
+    match interpreter._get_variable("total"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(5))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("local"){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("local")))
+    }
+    let tokens = lexer.lex("if true { secret = 1; r = read_local(); };").unwrap();
+    match interpreter.execute_instructions(&parser.parse_instructions(tokens).unwrap()){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error("secret")))
+    }
+}
+
+#[test]
+fn test_return_outside_function(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let tokens = lexer.lex("while true { return 1; };").unwrap();
+    match parser.parse_instructions(tokens){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_parser_error("'return' outside of a function")))
+    }
+}
+
+#[test]
+fn test_call_registered_function_from_source(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    interpreter.register_function("max", 2, |arguments| {
+        if arguments[0].gt(&arguments[1])? { Ok(arguments[0].clone()) } else { Ok(arguments[1].clone()) }
+    });
+    let tokens = lexer.lex("fn clamp(x) { return max(x, 0); }; a = clamp(-4); b = clamp(max(3, 9));").unwrap();
+    match interpreter.execute_instructions(&parser.parse_instructions(tokens).unwrap()){
+        Ok(_) => {},
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(0))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("b"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(9))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_parser_error_span(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let tokens = lexer.lex("a = 1;\nb = (2 + 3;").unwrap();
+    match parser.parse_instructions(tokens){
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_parser_error("')' expected but none found")));
+            assert_eq!(e.get_span().map(|span| (span.get_line(), span.get_column())), Some((2, 5)));
+        }
+    }
+    let tokens = lexer.lex("while a < 3 {\n  a = a + 1;\n").unwrap();
+    match parser.parse_instructions(tokens){
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_parser_error("'}' expected but none found")));
+            assert_eq!(e.get_span().map(|span| (span.get_line(), span.get_column())), Some((1, 13)));
+        }
+    }
+    // A missing value is reported where it was expected, on the token that ends the statement or the block.
+    for (source, line, column) in [("a = 1;\nb = ;", 2, 5), ("a = 1;\nc = (1 + ;", 2, 10), ("if true {\n  b = }", 2, 7)] {
+        let tokens = lexer.lex(source).unwrap();
+        match parser.parse_instructions(tokens){
+            Ok(_) => assert!(false),
+            Err(e) => {
+                assert!(e._equals(&CustomError::new_parser_error("Value expected but none found")));
+                assert_eq!(e.get_span().map(|span| (span.get_line(), span.get_column())), Some((line, column)));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_runtime_error_span(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    let tokens = lexer.lex("fn f(x) {\n  return x + y;\n};\nf(1);").unwrap();
+    match interpreter.execute_instructions(&parser.parse_instructions(tokens).unwrap()){
+        Ok(_) => assert!(false),
+        Err(e) => {
+            assert!(e._equals(&CustomError::new_variable_not_found_error("y")));
+            assert_eq!(e.get_span().map(|span| (span.get_line(), span.get_column())), Some((2, 14)));
+        }
+    }
+    let tokens = lexer.lex("a = 1;\nb = a + \"x\" * 2;").unwrap();
+    match interpreter.execute_instructions(&parser.parse_instructions(tokens).unwrap()){
+        Ok(_) => assert!(false),
+        Err(e) => {
+            let span = e.get_span().unwrap();
+            assert_eq!((span.get_line(), span.get_column(), span.get_start(), span.get_end()), (2, 5, 11, 22));
+        }
+    }
+}
+
+#[test]
+fn test_parse_with_diagnostics(){
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    let mut interpreter = get_interpreter();
+    let source = "a = 1;\nb = (2;\nwhile a < 3 {\n  c = ;\n  a = a + 1;\n};\n}\nd = a * 2;";
+    let (instructions, errors) = parser.parse_instructions_with_diagnostics(lexer.lex(source).unwrap());
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0]._equals(&CustomError::new_parser_error("')' expected but none found")));
+    assert!(errors[1]._equals(&CustomError::new_parser_error("Value expected but none found")));
+    assert!(errors[2]._equals(&CustomError::new_parser_error("Unexpected token: }")));
+    let lines: Vec<usize> = errors.iter().map(|e| e.get_span().unwrap().get_line()).collect();
+    assert_eq!(lines, vec![2, 4, 7]);
+    assert_eq!(instructions.len(), 3);
+    match interpreter.execute_instructions(&instructions){
+        Ok(_) => {},
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("d"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(6))),
+        Err(_) => assert!(false)
+    }
+    match parser.parse_instructions(lexer.lex(source).unwrap()){
+        Ok(_) => assert!(false),
+        Err(e) => assert!(e._equals(&CustomError::new_parser_error("')' expected but none found")))
+    }
+}
+
+fn parse_source(source: &str) -> Vec<Stmt> {
+    let lexer = Lexer::new();
+    let parser = Parser::new();
+    parser.parse_instructions(lexer.lex(source).unwrap()).unwrap()
+}
+
+#[test]
+fn test_parse_to_ast(){
+    let program = parse_source("a = 1 + 2 * x;");
+    assert_eq!(program.len(), 1);
+    match &program[0] {
+        Stmt::Assign{name, value: Expr::Binary{left, operator: '+', right, ..}, ..} => {
+            assert_eq!(name, "a");
+            assert_eq!(**left, Expr::Integer(1));
+            match right.as_ref() {
+                Expr::Binary{operator: '*', right, ..} => assert!(matches!(right.as_ref(), Expr::Variable{name, ..} if name == "x")),
+                _ => assert!(false)
+            }
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_print_ast(){
+    let source = "fn f(a, b) { return a * -b; }; \
+        if not x >= 1 and y { z = f(1, 2.5); } else if x == null { z = \"s\"; } else { z = !true; }; \
+        while i < 3 { i = i + 1; };";
+    let printed: Vec<String> = parse_source(source).iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec![
+        "fn f(a, b) { return (a * -b); };",
+        "if (!(x >= 1) and y) { z = f(1, 2.5); } else if (x == null) { z = \"s\"; } else { z = !true; };",
+        "while (i < 3) { i = (i + 1); };",
+    ]);
+    let reprinted: Vec<String> = parse_source(&printed.join(" ")).iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(reprinted, printed);
+}
+
+#[test]
+fn test_builders_produce_ast(){
+    let operation = Operation::new(Box::new(Variable::new("a")), Box::new(IntegerValue::new(2)), '*');
+    assert_eq!(operation.to_expr(), Expr::Binary{
+        left: Box::new(Expr::Variable{name: "a".to_string(), span: None, slot: Slot::Global}),
+        operator: '*',
+        right: Box::new(Expr::Integer(2)),
+        span: None
+    });
+    let parsed = parse_source("a * 2;");
+    match &parsed[0] {
+        Stmt::Expr(Expr::Binary{operator: '*', span: Some(span), ..}) => assert_eq!((span.get_start(), span.get_end()), (0, 5)),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_resolve_slots(){
+    let program = parse_source("fn f(a) { b = a; if a { b = 2; c = b; }; }; x = 1;");
+    match &program[..] {
+        [Stmt::Function(definition, Slot::Global), Stmt::Assign{slot: Slot::Global, ..}] => {
+            match &definition.body[..] {
+                [Stmt::Assign{value: Expr::Variable{slot: read, ..}, slot: write, ..}, Stmt::If{then_block, ..}] => {
+                    assert_eq!(*read, Slot::Local(1, 0));
+                    assert_eq!(*write, Slot::Local(0, 0));
+                    // `b` is the variable of the body assigned before the block, `c` a new one of the block.
+                    match &then_block[..] {
+                        [Stmt::Assign{slot: inner, ..}, Stmt::Assign{value: Expr::Variable{slot: read, ..}, slot: new, ..}] => {
+                            assert_eq!(*inner, Slot::Local(1, 0));
+                            assert_eq!(*read, Slot::Local(1, 0));
+                            assert_eq!(*new, Slot::Local(0, 0));
+                        },
+                        _ => assert!(false)
+                    }
+                },
+                _ => assert!(false)
+            }
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn test_resolved_variables_keep_scoping_rules(){
+    let mut interpreter = execute_source("
+        fn count() { counter = counter + 1; };
+        counter = 0;
+        count();
+        count();
+        fn update_outer() { x = 1; if true { x = x + 1; y = 5; }; return x; };
+        a = update_outer();
+        fn maybe(flag) { if flag { local = 1; }; local = 2; return local; };
+        b = maybe(true) + maybe(false);
+        if true { hidden = 1; };");
+    match interpreter._get_variable("counter"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(2))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(2))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("b"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(4))),
+        Err(_) => assert!(false)
+    }
+    for name in ["x", "y", "local", "hidden"] {
+        match interpreter._get_variable(name){
+            Ok(_) => assert!(false),
+            Err(e) => assert!(e._equals(&CustomError::new_variable_not_found_error(name)))
+        }
+    }
+}
+
+#[test]
+fn test_global_variables_across_programs(){
+    let mut interpreter = execute_source("if true { y = 1; }; y = 2; fn bump() { y = y + 1; };");
+    let tokens = match Lexer::new().lex("if true { y = y + 10; }; bump(); z = y;") {
+        Ok(tokens) => tokens,
+        Err(_) => {assert!(false); return}
+    };
+    // A later program finds the global variables of the earlier ones, from blocks and functions alike.
+    match Parser::new().parse_instructions(tokens).and_then(|instructions| interpreter.execute_instructions(&instructions)) {
+        Ok(_) => (),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("z"){
+        Ok(value) => assert!(eq_values(&value, &Value::Integer(13))),
+        Err(_) => assert!(false)
+    }
+}
+
+fn execute_source_error(source: &str) -> CustomError {
+    let tokens = match Lexer::new().lex(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return e,
+    };
+    let instructions = match Parser::new().parse_instructions(tokens) {
+        Ok(instructions) => instructions,
+        Err(e) => return e,
+    };
+    match get_interpreter().execute_instructions(&instructions) {
+        Ok(_) => panic!("{} should fail", source),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn test_modulo_precedence(){
+    let mut interpreter = execute_source("a = 1 + 7 % 4 * 2; b = 10 % 0.0 != 10 % 0.0;");
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(matches!(value, Value::Integer(7))),
+        Err(_) => assert!(false)
+    }
+    match interpreter._get_variable("b"){
+        Ok(value) => assert!(matches!(value, Value::Boolean(true))),
+        Err(_) => assert!(false)
+    }
+    let e = execute_source_error("a = 0;\nb = 5 % a;");
+    assert!(e._equals(&CustomError::new_division_by_zero_error()));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:5".to_string()));
+}
+
+#[test]
+fn test_recursion_limit(){
+    let e = execute_source_error("fn forever(n) { return forever(n + 1); }; forever(0);");
+    assert!(e._equals(&CustomError::new_recursion_limit_error(1000)));
+    // Deep but bounded recursion runs, whatever the stack of the thread running it.
+    let mut interpreter = execute_source("fn depth(n) { if n == 0 { return 0; }; return 1 + depth(n - 1); }; d = depth(900);");
+    match interpreter._get_variable("d"){
+        Ok(value) => assert!(matches!(value, Value::Integer(900))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_nesting_limit(){
+    let source = format!("a = {}1{};", "(".repeat(100_000), ")".repeat(100_000));
+    let e = execute_source_error(&source);
+    assert!(e._equals(&CustomError::new_parser_error("Too deeply nested, at most 256 levels are allowed")));
+    let source = format!("{}a = 1;{}", "while true { ".repeat(1_000), " };".repeat(1_000));
+    let e = execute_source_error(&source);
+    assert!(e._equals(&CustomError::new_parser_error("Too deeply nested, at most 256 levels are allowed")));
+    let mut interpreter = execute_source(&format!("a = {}1{};", "(".repeat(100), ")".repeat(100)));
+    match interpreter._get_variable("a"){
+        Ok(value) => assert!(matches!(value, Value::Integer(1))),
+        Err(_) => assert!(false)
+    }
+    // Chained operators, calls and indexes nest the expression too.
+    for source in [format!("x = 0{};", " + 1".repeat(100_000)), format!("xs = [0]; ys = xs{};", "[0:]".repeat(150_000))] {
+        let e = execute_source_error(&source);
+        assert!(e._equals(&CustomError::new_parser_error("Too deeply nested, at most 256 levels are allowed")));
+    }
+    let mut interpreter = execute_source(&format!("x = 0{}; xs = [0]; ys = xs{};", " + 1".repeat(200), "[0:]".repeat(200)));
+    match interpreter._get_variable("x"){
+        Ok(value) => assert!(matches!(value, Value::Integer(200))),
+        Err(_) => assert!(false)
+    }
+}
+
+#[test]
+fn test_lists(){
+    let source = "xs = [1, 2 + 1, [\"a\"]]; ys = xs; ys[0] = -1; xs[2][0] = xs[-1][0] * 2; \
+        first = xs[0]; tail = xs[1:]; head = xs[:-1]; n = len(xs + [4]); empty = [][:];";
+    let mut interpreter = execute_source(source);
+    let expected = [
+        ("xs", "[-1, 3, [\"aa\"]]"),
+        ("first", "-1"),
+        ("tail", "[3, [\"aa\"]]"),
+        ("head", "[-1, 3]"),
+        ("n", "4"),
+        ("empty", "[]"),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+    let e = execute_source_error("xs = [1];\nxs[1] = 2;");
+    assert!(e._equals(&CustomError::new_index_error(1, 1)));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:1".to_string()));
+}
+
+#[test]
+fn test_list_syntax(){
+    let printed: Vec<String> = parse_source("a = [1, [x], f(y)[0]]; b = a[1:][:2][i:]; a[0] = [];").iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec!["a = [1, [x], f(y)[0]];", "b = a[1:][:2][i:];", "a[0] = [];"]);
+    match &parse_source("a[:];")[0] {
+        Stmt::Expr(Expr::Slice{start: None, end: None, ..}) => (),
+        _ => assert!(false)
+    }
+    let errors = [
+        ("a = [1, 2;", "']' expected but none found"),
+        ("a = b[0;", "']' expected but none found"),
+        ("a = b[];", "Index expected but none found"),
+        ("a[0:1] = 2;", "Cannot assign to a[0:1]"),
+        ("f(1) = 2;", "Cannot assign to f(1)"),
+    ];
+    for (source, message) in errors {
+        let e = execute_source_error(source);
+        assert!(e._equals(&CustomError::new_parser_error(message)), "{}: {}", source, e);
+    }
+}
+
+#[test]
+fn test_maps(){
+    let source = "m = {\"a\": 1, \"b\": [2], 3: {}}; m[\"c\"] = m[\"a\"] + 1; m[3][\"x\"] = true; \
+        fn get(map, key) { if (has(map, key)) { return map[key]; }; return null; }; \
+        a = get(m, \"b\")[0]; missing = get(m, \"z\"); \
+        found = null; if (m != {}) { found = {\"k\": \"v\"}[\"k\"]; };";
+    let mut interpreter = execute_source(source);
+    let expected = [
+        ("m", "{\"a\": 1, \"b\": [2], 3: {\"x\": true}, \"c\": 2}"),
+        ("a", "2"),
+        ("missing", "null"),
+        ("found", "v"),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+    let e = execute_source_error("m = {};\nx = m[\"k\"];");
+    assert!(e._equals(&CustomError::new_key_error("\"k\"")));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:5".to_string()));
+}
+
+#[test]
+fn test_map_syntax(){
+    let printed: Vec<String> = parse_source("m = {\"a\": 1, x: [y], 2: {}}; while (m == {}) { m = {1: 2}[1]; };").iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec!["m = {\"a\": 1, x: [y], 2: {}};", "while (m == {}) { m = {1: 2}[1]; };"]);
+    let errors = [
+        ("m = {\"a\" 1};", "':' expected after a map key"),
+        ("m = {\"a\": 1;", "'}' expected but none found"),
+        // Without parentheses, the '{' of a map in a condition opens the block.
+        ("if m == {} { a = 1; };", "Value expected but none found"),
+        ("if {\"a\": 1} == m { a = 1; };", "Map literal found where a block was expected"),
+    ];
+    for (source, message) in errors {
+        let e = execute_source_error(source);
+        assert!(e._equals(&CustomError::new_parser_error(message)), "{}: {}", source, e);
+    }
+    // A map with entries is told apart from a block, and reported at its '{'.
+    let e = execute_source_error("m = 1;\nfor k, v in {\"a\": 1} { };");
+    assert!(e._equals(&CustomError::new_parser_error("Map literal found where a block was expected")));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:13".to_string()));
+    assert_eq!(e.get_notes(), &["wrap the map literal in parentheses".to_string()]);
+}
+
+#[test]
+fn test_for_loops(){
+    let source = "squares = []; for i in 1..4 { squares = squares + [i * i]; }; \
+        letters = \"\"; for ch in \"krab\" { letters = ch + letters; }; \
+        pairs = []; for k, v in ({\"x\": 1, \"y\": 2}) { pairs = pairs + [k, v]; }; \
+        ks = []; for k in ({\"x\": 1, \"y\": 2}) { ks = ks + [k]; }; \
+        xs = [1, 2]; seen = []; for x in xs { if x == 1 { xs[1] = 20; }; seen = seen + [x]; }; \
+        fn find(list, target) { for i in 0..len(list) { if list[i] == target { return i; }; }; return -1; }; \
+        found = find([5, 6, 7], 7); missing = find([], 7); \
+        count = 0; for i in 0..3 { for j in i..3 { count = count + 1; }; };";
+    let mut interpreter = execute_source(source);
+    let expected = [
+        ("squares", "[1, 4, 9]"),
+        ("letters", "bark"),
+        ("pairs", "[\"x\", 1, \"y\", 2]"),
+        ("ks", "[\"x\", \"y\"]"),
+        ("seen", "[1, 20]"),
+        ("found", "2"),
+        ("missing", "-1"),
+        ("count", "6"),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+    let e = execute_source_error("a = 1;\nfor x in a { };");
+    assert!(e._equals(&CustomError::new_type_error("Cannot iterate over Integer(1)")));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:1".to_string()));
+}
+
+#[test]
+fn test_for_syntax(){
+    let printed: Vec<String> = parse_source("for i in 0..n + 1 { x = i; }; for k, v in m { x = k; };").iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec!["for i in (0..(n + 1)) { x = i; };", "for k, v in m { x = k; };"]);
+    let errors = [
+        ("for in x { };", "Loop variable expected after 'for'"),
+        ("for x y { };", "'in' expected after the loop variables"),
+        ("for a, b, c in x { };", "'in' expected after the loop variables"),
+        ("for a, a in x { };", "Duplicate loop variable a"),
+        ("for x in y;", "'{' expected but none found"),
+    ];
+    for (source, message) in errors {
+        let e = execute_source_error(source);
+        assert!(e._equals(&CustomError::new_parser_error(message)), "{}: {}", source, e);
+    }
+}
+
+#[test]
+fn test_break_and_continue(){
+    let source = "odds = []; i = 0; while true { i = i + 1; if i > 9 { break; }; if i % 2 == 0 { continue; }; odds = odds + [i]; }; \
+        pairs = []; for a in 0..4 { for b in 0..4 { if b > a { break; }; if a == b { continue; }; pairs = pairs + [[a, b]]; }; }; \
+        fn first_negative(list) { position = -1; for i in 0..len(list) { if true { if list[i] < 0 { position = i; break; }; }; }; \
+            after = position * 10; return after; }; \
+        found = first_negative([3, -1, -2]); none = first_negative([]); \
+        fn stops(n) { while true { fn inner() { return 1; }; if n == 0 { break; }; n = n - 1; }; return n; }; stopped = stops(3);";
+    let mut interpreter = execute_source(source);
+    let expected = [
+        ("odds", "[1, 3, 5, 7, 9]"),
+        ("i", "10"),
+        ("pairs", "[[1, 0], [2, 0], [2, 1], [3, 0], [3, 1], [3, 2]]"),
+        ("found", "10"),
+        ("none", "-10"),
+        ("stopped", "0"),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+}
+
+#[test]
+fn test_break_and_continue_syntax(){
+    let printed: Vec<String> = parse_source("while x { break; }; for i in l { continue; };").iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec!["while x { break; };", "for i in l { continue; };"]);
+    let errors = [
+        ("break;", "'break' outside of a loop"),
+        ("if true { continue; };", "'continue' outside of a loop"),
+        ("while true { fn f() { break; }; };", "'break' outside of a loop"),
+        ("while true { break 1; };", "Unexpected token after 'break': 1"),
+    ];
+    for (source, message) in errors {
+        let e = execute_source_error(source);
+        assert!(e._equals(&CustomError::new_parser_error(message)), "{}: {}", source, e);
+    }
+    let e = execute_source_error("a = 1;\nbreak;");
+    assert_eq!(e.get_notes(), &["'break' can only be used inside the body of a 'while' or 'for' loop".to_string()]);
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:1".to_string()));
+}
+
+#[test]
+fn test_try_catch(){
+    let source = "fn parse(s) { try { return int(s); } catch e { return e[\"kind\"]; }; }; \
+        numbers = [parse(\"12\"), parse(\"x\")]; \
+        steps = []; fn run() { try { steps = steps + [\"try\"]; throw \"stop\"; } catch e { steps = steps + [e]; } finally { steps = steps + [\"finally\"]; }; }; run(); \
+        fn override() { try { return 1; } finally { return 2; }; }; overridden = override(); \
+        message = null; try { try { undefined; } finally { steps = steps + [\"inner\"]; }; } catch e { message = e[\"message\"]; }; \
+        total = 0; for i in 0..5 { try { if i % 2 == 0 { throw i; }; } catch e { total = total + e; continue; } finally { total = total + 100; }; }; \
+        fn depth(n) { if n == 0 { throw \"bottom\"; }; x = n; r = depth(n - 1); return r; }; \
+        caught = null; try { depth(50); } catch e { caught = e; };";
+    let mut interpreter = execute_source(source);
+    let expected = [
+        ("numbers", "[12, \"ConversionError\"]"),
+        ("steps", "[\"try\", \"stop\", \"finally\", \"inner\"]"),
+        ("overridden", "2"),
+        ("message", "Variable undefined does not exist"),
+        ("total", "506"),
+        ("caught", "bottom"),
+    ];
+    for (name, value) in expected {
+        match interpreter._get_variable(name){
+            Ok(v) => assert_eq!(v._to_string(), value, "{}", name),
+            Err(_) => assert!(false)
+        }
+    }
+    let e = execute_source_error("a = 1;\nthrow {\"a\": a};");
+    assert!(e._equals(&CustomError::new_thrown_error(Value::new_map(vec![(Value::new_string("a"), Value::new_integer(1))]).unwrap())));
+    assert_eq!(e.get_message(), "{\"a\": 1}");
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:1".to_string()));
+}
+
+#[test]
+fn test_try_syntax(){
+    let printed: Vec<String> = parse_source("try { a(); } catch e { b(e); } finally { c(); }; try { a(); } finally { c(); }; throw [1];").iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(printed, vec!["try { a(); } catch e { b(e); } finally { c(); };", "try { a(); } finally { c(); };", "throw [1];"]);
+    let errors = [
+        ("try { a(); };", "'catch' or 'finally' expected after the 'try' block"),
+        ("try { a(); } catch { };", "Error variable expected after 'catch'"),
+        ("try { a(); } catch e;", "'{' expected but none found"),
+        ("try { a(); } finally { } catch e { };", "Unexpected token after '}': catch"),
+        ("throw;", "Value expected after 'throw'"),
+        ("while true { try { } finally { fn f() { continue; }; }; };", "'continue' outside of a loop"),
+    ];
+    for (source, message) in errors {
+        let e = execute_source_error(source);
+        assert!(e._equals(&CustomError::new_parser_error(message)), "{}: {}", source, e);
+    }
+}
+
+#[test]
+fn test_expression_statement_error(){
+    let e = execute_source_error("x = 1;\nprintln(1 +);");
+    assert!(e._equals(&CustomError::new_parser_error("Unexpected value: )")));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:12".to_string()));
+    let e = execute_source_error("x = 1;\nfoo(1, [2, 3);");
+    assert!(e._equals(&CustomError::new_parser_error("']' expected but none found")));
+    assert_eq!(e.get_span().map(|span| span.to_string()), Some("2:8".to_string()));
+}